@@ -0,0 +1,334 @@
+// Agenda
+//
+// Merges local tasks, cached Asana tasks, local events and cached Google
+// events into one timeline for today and the next days, in the user's zone.
+// Each day lists all-day events and due tasks first, then timed events by
+// start. Overdue tasks are listed on the first day. render_briefing turns the
+// same timeline into plain text. Ranges span at most a year unless everything
+// upcoming is asked for.
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::command;
+
+use crate::conflicts::{self, Interval};
+use crate::tasks::parse_task_date;
+use crate::{storage, timezones};
+
+const ASANA_CACHE_FILE: &str = "asana_tasks_cache.json";
+const MAX_DAYS: u32 = 366;
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    LocalTask,
+    AsanaTask,
+    LocalEvent,
+    GoogleEvent,
+}
+
+impl ItemKind {
+    fn label(self) -> &'static str {
+        match self {
+            ItemKind::LocalTask => "task",
+            ItemKind::AsanaTask => "Asana",
+            ItemKind::LocalEvent => "event",
+            ItemKind::GoogleEvent => "Google",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct AgendaItem {
+    kind: ItemKind,
+    // Google events are cached without an id
+    id: Option<String>,
+    title: String,
+    // Day the item is listed on
+    date: NaiveDate,
+    // Timed events only, in the user's zone
+    start: Option<DateTime<FixedOffset>>,
+    end: Option<DateTime<FixedOffset>>,
+    all_day: bool,
+    completed: bool,
+    overdue: bool,
+    project: Option<String>,
+    location: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Agenda {
+    timezone: String,
+    from: NaiveDate,
+    to: NaiveDate,
+    items: Vec<AgendaItem>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct AgendaOptions {
+    // Today plus this many following days, at most MAX_DAYS
+    days: u32,
+    // Everything from today on, however far out; days is ignored
+    all_upcoming: bool,
+    include_completed: bool,
+    include_overdue: bool,
+}
+
+impl Default for AgendaOptions {
+    fn default() -> Self {
+        AgendaOptions {
+            days: 0,
+            all_upcoming: false,
+            include_completed: false,
+            include_overdue: true,
+        }
+    }
+}
+
+fn non_empty(value: Option<&str>) -> Option<String> {
+    value.map(str::trim).filter(|v| !v.is_empty()).map(String::from)
+}
+
+fn load_asana_tasks() -> Result<Vec<Value>, String> {
+    let json = match storage::read_to_string(ASANA_CACHE_FILE)
+        .map_err(|e| format!("Failed to read Asana cache: {}", e))?
+    {
+        Some(json) => json,
+        None => return Ok(Vec::new()),
+    };
+    let value: Value = serde_json::from_str(&json).map_err(|e| format!("Failed to parse Asana cache: {}", e))?;
+    Ok(match value {
+        Value::Array(tasks) => tasks,
+        _ => Vec::new(),
+    })
+}
+
+fn event_item(kind: ItemKind, interval: Interval, location: Option<String>, from: NaiveDate) -> AgendaItem {
+    let zone = timezones::user_zone();
+    let start = interval.start.with_timezone(&zone).fixed_offset();
+    let end = interval.end.with_timezone(&zone).fixed_offset();
+    AgendaItem {
+        kind,
+        id: interval.id,
+        title: interval.title,
+        // Events that began before the range are listed on its first day
+        date: start.date_naive().max(from),
+        start: (!interval.all_day).then_some(start),
+        end: (!interval.all_day).then_some(end),
+        all_day: interval.all_day,
+        completed: false,
+        overdue: false,
+        project: None,
+        location,
+    }
+}
+
+fn build(options: &AgendaOptions) -> Result<Agenda, String> {
+    let zone = timezones::user_zone();
+    let today = Utc::now().with_timezone(&zone).date_naive();
+    let from = today;
+    let to = if options.all_upcoming {
+        // Late enough for any real date, early enough to resolve in every zone
+        NaiveDate::from_ymd_opt(9999, 12, 30).ok_or("Invalid agenda end")?
+    } else {
+        today + Duration::days(options.days.min(MAX_DAYS) as i64)
+    };
+
+    let mut items = Vec::new();
+    // A due task on [from, to], or an overdue one to list on the first day
+    let mut add_task = |kind, id, title: String, due: Option<NaiveDate>, completed: bool, project| {
+        let Some(due) = due else { return };
+        if completed && !options.include_completed {
+            return;
+        }
+        let overdue = due < from && !completed;
+        if (due < from && !(overdue && options.include_overdue)) || due > to {
+            return;
+        }
+        items.push(AgendaItem {
+            kind,
+            id: Some(id),
+            title,
+            date: due.max(from),
+            start: None,
+            end: None,
+            all_day: false,
+            completed,
+            overdue,
+            project,
+            location: None,
+        });
+    };
+
+    for task in crate::load_local_tasks()? {
+        let due = parse_task_date(&task.date);
+        add_task(ItemKind::LocalTask, task.id, task.title, due, task.completed, non_empty(Some(&task.project)));
+    }
+    for task in load_asana_tasks()? {
+        let Some(title) = non_empty(task["name"].as_str()) else { continue };
+        let id = task["gid"].as_str().map(String::from).unwrap_or_else(|| title.clone());
+        let due = task["due_at"]
+            .as_str()
+            .or(task["due_on"].as_str())
+            .and_then(parse_task_date);
+        let project = non_empty(task["projects"][0]["name"].as_str());
+        add_task(ItemKind::AsanaTask, id, title, due, task["completed"].as_bool().unwrap_or(false), project);
+    }
+
+    let range_start = timezones::midnight(zone, from).ok_or("Invalid agenda start")?;
+    let range_end = to
+        .succ_opt()
+        .and_then(|day| timezones::midnight(zone, day))
+        .ok_or("Invalid agenda end")?;
+    let in_range = |interval: &Interval| interval.end > range_start && interval.start < range_end;
+
+    for event in crate::load_local_events()? {
+        let location = non_empty(event.location.as_deref());
+        if let Some(interval) = conflicts::local_interval(&event).filter(in_range) {
+            items.push(event_item(ItemKind::LocalEvent, interval, location, from));
+        }
+    }
+    for event in crate::load_google_events_cache()? {
+        let location = non_empty(event.location.as_deref());
+        if let Some(interval) = conflicts::google_interval(&event).filter(in_range) {
+            items.push(event_item(ItemKind::GoogleEvent, interval, location, from));
+        }
+    }
+
+    items.sort_by(|a, b| {
+        (a.date, a.start.is_some(), a.start, !a.overdue, a.title.to_lowercase())
+            .cmp(&(b.date, b.start.is_some(), b.start, !b.overdue, b.title.to_lowercase()))
+    });
+
+    Ok(Agenda {
+        timezone: zone.name().to_string(),
+        from,
+        to,
+        items,
+    })
+}
+
+/// Renders an agenda as a plain-text briefing.
+pub fn render_briefing(agenda: &Agenda) -> String {
+    let mut out = if agenda.from == agenda.to {
+        format!("Agenda for {} ({})\n", agenda.from.format("%A, %-d %B %Y"), agenda.timezone)
+    } else {
+        format!(
+            "Agenda for {} to {} ({})\n",
+            agenda.from.format("%-d %B"),
+            agenda.to.format("%-d %B %Y"),
+            agenda.timezone
+        )
+    };
+    if agenda.items.is_empty() {
+        out.push_str("\nNothing scheduled.\n");
+        return out;
+    }
+
+    let overdue: Vec<&AgendaItem> = agenda.items.iter().filter(|i| i.overdue).collect();
+    if !overdue.is_empty() {
+        out.push_str("\nOverdue\n");
+        for item in overdue {
+            out.push_str(&format!("  [ ] {} ({})\n", item.title, item.kind.label()));
+        }
+    }
+
+    // Items are sorted by day
+    let mut day = None;
+    for item in agenda.items.iter().filter(|i| !i.overdue) {
+        if day != Some(item.date) {
+            day = Some(item.date);
+            out.push_str(&format!("\n{}\n", item.date.format("%A, %-d %B")));
+        }
+        let when = match (item.start, item.end) {
+            (Some(start), Some(end)) => format!("{}-{}", start.format("%H:%M"), end.format("%H:%M")),
+            _ if item.all_day => "all day".to_string(),
+            _ => "due".to_string(),
+        };
+        let check = match item.kind {
+            ItemKind::LocalTask | ItemKind::AsanaTask if item.completed => "[x] ",
+            ItemKind::LocalTask | ItemKind::AsanaTask => "[ ] ",
+            _ => "",
+        };
+        out.push_str(&format!("  {:<11}  {}{} ({})", when, check, item.title, item.kind.label()));
+        if let Some(location) = &item.location {
+            out.push_str(&format!(" @ {}", location));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Tasks due and events happening today and in the next `days` days.
+#[command]
+pub fn get_agenda(options: Option<AgendaOptions>) -> Result<Agenda, String> {
+    build(&options.unwrap_or_default())
+}
+
+#[command]
+pub fn get_daily_briefing(options: Option<AgendaOptions>) -> Result<String, String> {
+    Ok(render_briefing(&build(&options.unwrap_or_default())?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, d).unwrap()
+    }
+
+    fn item(kind: ItemKind, title: &str, date: NaiveDate) -> AgendaItem {
+        AgendaItem {
+            kind,
+            id: None,
+            title: title.to_string(),
+            date,
+            start: None,
+            end: None,
+            all_day: false,
+            completed: false,
+            overdue: false,
+            project: None,
+            location: None,
+        }
+    }
+
+    #[test]
+    fn renders_overdue_items_and_days() {
+        let start = DateTime::parse_from_rfc3339("2024-05-11T09:00:00+02:00").unwrap();
+        let end = DateTime::parse_from_rfc3339("2024-05-11T10:30:00+02:00").unwrap();
+        let agenda = Agenda {
+            timezone: "Europe/Berlin".to_string(),
+            from: day(10),
+            to: day(12),
+            items: vec![
+                AgendaItem { overdue: true, ..item(ItemKind::LocalTask, "Taxes", day(10)) },
+                AgendaItem { completed: true, ..item(ItemKind::AsanaTask, "Report", day(10)) },
+                AgendaItem {
+                    start: Some(start),
+                    end: Some(end),
+                    location: Some("Office".to_string()),
+                    ..item(ItemKind::GoogleEvent, "Standup", day(11))
+                },
+            ],
+        };
+
+        let briefing = render_briefing(&agenda);
+        assert_eq!(
+            briefing,
+            "Agenda for 10 May to 12 May 2024 (Europe/Berlin)\n\
+             \nOverdue\n  [ ] Taxes (task)\n\
+             \nFriday, 10 May\n  due          [x] Report (Asana)\n\
+             \nSaturday, 11 May\n  09:00-10:30  Standup (Google) @ Office\n"
+        );
+    }
+
+    #[test]
+    fn renders_an_empty_day() {
+        let agenda = Agenda { timezone: "UTC".to_string(), from: day(10), to: day(10), items: Vec::new() };
+        assert_eq!(render_briefing(&agenda), "Agenda for Friday, 10 May 2024 (UTC)\n\nNothing scheduled.\n");
+    }
+}
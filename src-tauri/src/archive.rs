@@ -0,0 +1,322 @@
+// Task archive
+//
+// Once enabled, tasks completed more than archive_after_days ago move out of
+// the task store into task_archive.json whenever tasks are loaded or saved.
+// Subtasks move together with their top-level task, so a tree is only
+// archived once every task in it qualifies. Archived tasks can be searched
+// and restored; purging deletes them for good and leaves their ids behind so
+// sync can delete the Firestore copies as well.
+
+use std::collections::{HashMap, HashSet};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+use crate::{attachments, schema, storage, tasks, Task};
+
+const SETTINGS_FILE: &str = "archive_settings.json";
+const PURGED_FILE: &str = "purged_tasks.json";
+// Longer thresholds would overflow the cutoff date
+const MAX_DAYS: u32 = 100 * 366;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ArchiveSettings {
+    enabled: bool,
+    archive_after_days: u32,
+    // Default threshold for purge_archived_tasks
+    purge_after_days: Option<u32>,
+}
+
+impl Default for ArchiveSettings {
+    fn default() -> Self {
+        // Off until the user opts in, so completed tasks never vanish on upgrade
+        ArchiveSettings {
+            enabled: false,
+            archive_after_days: 30,
+            purge_after_days: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ArchivedTask {
+    #[serde(flatten)]
+    pub task: Task,
+    #[serde(default)]
+    pub archived_at: String,
+}
+
+#[derive(Serialize)]
+pub struct ArchivedIds {
+    // Kept out of the task store, but still present in Firestore
+    archived: Vec<String>,
+    // Deleted for good; sync removes them from Firestore
+    purged: Vec<String>,
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+// The moment before which a task counts as old enough.
+fn cutoff(days: u32) -> DateTime<Utc> {
+    Utc::now() - Duration::days(days.min(MAX_DAYS) as i64)
+}
+
+fn load_settings() -> Result<ArchiveSettings, String> {
+    let json = match storage::read_to_string(SETTINGS_FILE)
+        .map_err(|e| format!("Failed to read archive settings: {}", e))?
+    {
+        Some(json) => json,
+        None => return Ok(ArchiveSettings::default()),
+    };
+
+    serde_json::from_str(&json).map_err(|e| format!("Failed to deserialize archive settings: {}", e))
+}
+
+pub fn load_archive() -> Result<Vec<ArchivedTask>, String> {
+    schema::load(&schema::ARCHIVED_TASKS)
+}
+
+fn load_purged() -> Result<Vec<String>, String> {
+    match storage::read_to_string(PURGED_FILE)
+        .map_err(|e| format!("Failed to read purged tasks: {}", e))?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| format!("Failed to deserialize purged tasks: {}", e)),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn save_purged(ids: &[String]) -> Result<(), String> {
+    let json = serde_json::to_string(ids)
+        .map_err(|e| format!("Failed to serialize purged tasks: {}", e))?;
+    storage::write(PURGED_FILE, json.as_bytes())
+        .map_err(|e| format!("Failed to write purged tasks: {}", e))
+}
+
+fn before(timestamp: Option<&str>, cutoff: DateTime<Utc>) -> bool {
+    timestamp
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .is_some_and(|t| t < cutoff)
+}
+
+// Completed before the cutoff and not touched since, so restored tasks stay
+// out of the archive for another full period.
+fn is_archivable(task: &Task, cutoff: DateTime<Utc>) -> bool {
+    task.completed
+        && before(task.completed_on.as_deref(), cutoff)
+        && (task.updated_at.is_none() || before(task.updated_at.as_deref(), cutoff))
+}
+
+fn root_of<'a>(task: &'a Task, by_id: &HashMap<&str, &'a Task>) -> &'a str {
+    let mut current = task;
+    // Parent links are cycle-free after roll_up_completion; the bound is a safeguard
+    for _ in 0..by_id.len() {
+        match current.parent_id.as_deref().and_then(|id| by_id.get(id)) {
+            Some(parent) => current = parent,
+            None => break,
+        }
+    }
+    &current.id
+}
+
+/// Moves tasks that are due for archiving from `tasks` into the archive.
+/// Returns the number of tasks moved.
+pub fn apply_policy(tasks: &mut Vec<Task>) -> Result<usize, String> {
+    let settings = load_settings()?;
+    if !settings.enabled {
+        return Ok(0);
+    }
+    let cutoff = cutoff(settings.archive_after_days);
+
+    let by_id: HashMap<&str, &Task> = tasks.iter().map(|t| (t.id.as_str(), t)).collect();
+    let mut tree_archivable: HashMap<&str, bool> = HashMap::new();
+    for task in tasks.iter() {
+        let entry = tree_archivable.entry(root_of(task, &by_id)).or_insert(true);
+        *entry = *entry && is_archivable(task, cutoff);
+    }
+    let moving: HashSet<String> = tasks
+        .iter()
+        .filter(|t| tree_archivable[root_of(t, &by_id)])
+        .map(|t| t.id.clone())
+        .collect();
+    if moving.is_empty() {
+        return Ok(0);
+    }
+
+    let archived_at = now();
+    let mut archive = load_archive()?;
+    archive.retain(|a| !moving.contains(&a.task.id));
+    let (moved, kept): (Vec<Task>, Vec<Task>) = tasks.drain(..).partition(|t| moving.contains(&t.id));
+    archive.extend(moved.into_iter().map(|task| ArchivedTask {
+        task,
+        archived_at: archived_at.clone(),
+    }));
+    schema::save(&schema::ARCHIVED_TASKS, &archive)?;
+
+    *tasks = kept;
+    Ok(moving.len())
+}
+
+pub fn archived_ids() -> Result<HashSet<String>, String> {
+    Ok(load_archive()?.into_iter().map(|a| a.task.id).collect())
+}
+
+/// Archives tasks again, given as their stored JSON records.
+pub fn add_to_archive(records: Vec<serde_json::Value>) -> Result<(), String> {
+    let archived_at = now();
+    let mut archive = load_archive()?;
+    for record in records {
+        let task: Task = serde_json::from_value(record)
+            .map_err(|e| format!("Failed to deserialize archived task: {}", e))?;
+        archive.retain(|a| a.task.id != task.id);
+        archive.push(ArchivedTask {
+            task,
+            archived_at: archived_at.clone(),
+        });
+    }
+    schema::save(&schema::ARCHIVED_TASKS, &archive)
+}
+
+/// Drops tasks that are back in the task store from the archive.
+pub fn remove_from_archive(ids: &HashSet<String>) -> Result<(), String> {
+    let mut archive = load_archive()?;
+    let before = archive.len();
+    archive.retain(|a| !ids.contains(&a.task.id));
+    if archive.len() != before {
+        schema::save(&schema::ARCHIVED_TASKS, &archive)?;
+    }
+    Ok(())
+}
+
+#[command]
+pub fn get_archive_settings() -> Result<ArchiveSettings, String> {
+    load_settings()
+}
+
+#[command]
+pub fn save_archive_settings(settings: ArchiveSettings) -> Result<(), String> {
+    let longest = settings.purge_after_days.unwrap_or(0).max(settings.archive_after_days);
+    if longest > MAX_DAYS {
+        return Err(format!("Archive thresholds can be at most {} days", MAX_DAYS));
+    }
+    let json = serde_json::to_string(&settings)
+        .map_err(|e| format!("Failed to serialize archive settings: {}", e))?;
+    storage::write(SETTINGS_FILE, json.as_bytes())
+        .map_err(|e| format!("Failed to write archive settings: {}", e))
+}
+
+/// Archived tasks, most recently archived first, optionally filtered by text.
+#[command]
+pub fn list_archived_tasks(text: Option<String>) -> Result<Vec<ArchivedTask>, String> {
+    let text = text.map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty());
+    let mut archive: Vec<ArchivedTask> = load_archive()?
+        .into_iter()
+        .filter(|a| {
+            let task = &a.task;
+            text.as_ref().is_none_or(|text| {
+                task.title.to_lowercase().contains(text)
+                    || task.description.to_lowercase().contains(text)
+                    || task.project.to_lowercase().contains(text)
+                    || task.tags.iter().any(|t| t.to_lowercase().contains(text))
+            })
+        })
+        .collect();
+    archive.sort_by(|a, b| b.archived_at.cmp(&a.archived_at));
+    Ok(archive)
+}
+
+#[command]
+pub fn list_archived_task_ids() -> Result<ArchivedIds, String> {
+    Ok(ArchivedIds {
+        archived: load_archive()?.into_iter().map(|a| a.task.id).collect(),
+        purged: load_purged()?,
+    })
+}
+
+/// Archives due tasks right away instead of on the next save.
+#[command]
+pub fn archive_completed_tasks() -> Result<usize, String> {
+    let mut tasks: Vec<Task> = schema::load(&schema::TASKS)?;
+    let moved = apply_policy(&mut tasks)?;
+    if moved > 0 {
+        tasks::store(&tasks)?;
+    }
+    Ok(moved)
+}
+
+/// Moves archived tasks, with their archived subtasks, back into the task
+/// store. Returns the updated tasks.
+#[command]
+pub fn restore_archived_tasks(ids: Vec<String>) -> Result<Vec<Task>, String> {
+    let mut archive = load_archive()?;
+    let mut restoring: HashSet<String> = ids.into_iter().collect();
+    loop {
+        let before = restoring.len();
+        for archived in &archive {
+            if archived.task.parent_id.as_ref().is_some_and(|p| restoring.contains(p)) {
+                restoring.insert(archived.task.id.clone());
+            }
+        }
+        if restoring.len() == before {
+            break;
+        }
+    }
+
+    let (restored, kept): (Vec<ArchivedTask>, Vec<ArchivedTask>) =
+        archive.drain(..).partition(|a| restoring.contains(&a.task.id));
+    if restored.is_empty() {
+        return Err("None of the tasks are archived".to_string());
+    }
+
+    let timestamp = now();
+    let mut tasks: Vec<Task> = schema::load(&schema::TASKS)?;
+    tasks.retain(|t| !restoring.contains(&t.id));
+    tasks.extend(restored.into_iter().map(|a| Task {
+        updated_at: Some(timestamp.clone()),
+        pending_sync: true,
+        ..a.task
+    }));
+
+    tasks::store(&tasks)?;
+    schema::save(&schema::ARCHIVED_TASKS, &kept)?;
+    Ok(tasks)
+}
+
+/// Permanently deletes tasks archived more than `older_than_days` days ago,
+/// falling back to the purge_after_days setting. Returns the number deleted.
+#[command]
+pub fn purge_archived_tasks(older_than_days: Option<u32>) -> Result<usize, String> {
+    let days = match older_than_days {
+        Some(days) => days,
+        None => load_settings()?
+            .purge_after_days
+            .ok_or_else(|| "No purge threshold given".to_string())?,
+    };
+    let cutoff = cutoff(days);
+
+    let archive = load_archive()?;
+    let (purged, kept): (Vec<ArchivedTask>, Vec<ArchivedTask>) = archive
+        .into_iter()
+        .partition(|a| before(Some(&a.archived_at), cutoff));
+    if purged.is_empty() {
+        return Ok(0);
+    }
+
+    let mut tombstones = load_purged()?;
+    tombstones.extend(purged.iter().map(|a| a.task.id.clone()));
+    save_purged(&tombstones)?;
+    schema::save(&schema::ARCHIVED_TASKS, &kept)?;
+    attachments::collect_garbage()?;
+    Ok(purged.len())
+}
+
+/// Forgets purged ids once sync has deleted them from Firestore.
+#[command]
+pub fn clear_purged_task_ids(ids: Vec<String>) -> Result<(), String> {
+    let mut tombstones = load_purged()?;
+    tombstones.retain(|id| !ids.contains(id));
+    save_purged(&tombstones)
+}
@@ -0,0 +1,227 @@
+// Task, event and note attachments
+//
+// Attached files are copied into attachments/<sha256>, named by the hash of
+// their content, so the same file attached twice is stored once. Tasks,
+// events and notes only keep references by hash. Blobs nobody references
+// any more are removed by collect_garbage; the undo journal counts as a
+// reference so undoing a removal never points at a deleted blob.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::command;
+
+use crate::schema;
+use crate::{archive, journal, notes, profiles, storage, Event, Task};
+
+const DIR: &str = "attachments";
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum AttachmentTarget {
+    Task,
+    Event,
+    Note,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Attachment {
+    pub hash: String,
+    // Original file name, used when exporting
+    pub name: String,
+    pub size: u64,
+    pub added_at: String,
+}
+
+#[derive(Serialize)]
+pub struct GarbageSummary {
+    removed: usize,
+    bytes: u64,
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+fn blob_file(hash: &str) -> String {
+    format!("{}/{}", DIR, hash)
+}
+
+fn is_hash(hash: &str) -> bool {
+    hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn not_found(target: AttachmentTarget, id: &str) -> String {
+    match target {
+        AttachmentTarget::Task => format!("Task '{}' does not exist", id),
+        AttachmentTarget::Event => format!("Event '{}' does not exist", id),
+        AttachmentTarget::Note => format!("Note '{}' does not exist", id),
+    }
+}
+
+// Runs `change` on the attachments of one task or event and saves the store.
+fn update<R>(
+    target: AttachmentTarget,
+    id: &str,
+    change: impl FnOnce(&mut Vec<Attachment>) -> Result<R, String>,
+) -> Result<R, String> {
+    match target {
+        AttachmentTarget::Task => {
+            let mut tasks = crate::load_local_tasks()?;
+            let task = tasks
+                .iter_mut()
+                .find(|t| t.id == id)
+                .ok_or_else(|| not_found(target, id))?;
+            let result = change(&mut task.attachments)?;
+            task.updated_at = Some(now());
+            task.pending_sync = true;
+            crate::save_local_tasks(tasks)?;
+            Ok(result)
+        }
+        AttachmentTarget::Event => {
+            let mut events = crate::load_local_events()?;
+            let event = events
+                .iter_mut()
+                .find(|e| e.id == id)
+                .ok_or_else(|| not_found(target, id))?;
+            let result = change(&mut event.attachments)?;
+            event.updated_at = Some(now());
+            event.pending_sync = true;
+            crate::store_local_events(&events)?;
+            Ok(result)
+        }
+        AttachmentTarget::Note => notes::update_attachments(id, change),
+    }
+}
+
+/// Writes `bytes` into the store unless they are there already. Returns the
+/// hash they are stored under.
+pub fn store_blob(bytes: &[u8]) -> Result<String, String> {
+    let hash = format!("{:x}", Sha256::digest(bytes));
+    if !profiles::data_file(&blob_file(&hash))?.exists() {
+        storage::write(&blob_file(&hash), bytes)
+            .map_err(|e| format!("Failed to store attachment: {}", e))?;
+    }
+    Ok(hash)
+}
+
+/// Copies a file into the attachment store and references it from a task,
+/// event or note. Attaching the same content twice returns the existing
+/// reference.
+#[command]
+pub fn attach_file(target: AttachmentTarget, id: String, path: String) -> Result<Attachment, String> {
+    let source = Path::new(&path);
+    let name = source
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| format!("'{}' is not a file", path))?
+        .to_string();
+    let bytes = fs::read(source).map_err(|e| format!("Failed to read attachment: {}", e))?;
+    let hash = format!("{:x}", Sha256::digest(&bytes));
+
+    update(target, &id, |attachments| {
+        if let Some(existing) = attachments.iter().find(|a| a.hash == hash) {
+            return Ok(existing.clone());
+        }
+        store_blob(&bytes)?;
+        let attachment = Attachment {
+            hash: hash.clone(),
+            name,
+            size: bytes.len() as u64,
+            added_at: now(),
+        };
+        attachments.push(attachment.clone());
+        Ok(attachment)
+    })
+}
+
+#[command]
+pub fn list_attachments(target: AttachmentTarget, id: String) -> Result<Vec<Attachment>, String> {
+    match target {
+        AttachmentTarget::Task => crate::load_local_tasks()?
+            .into_iter()
+            .find(|t| t.id == id)
+            .map(|t| t.attachments),
+        AttachmentTarget::Event => crate::load_local_events()?
+            .into_iter()
+            .find(|e| e.id == id)
+            .map(|e| e.attachments),
+        AttachmentTarget::Note => notes::load_notes()?
+            .into_iter()
+            .find(|n| n.id == id)
+            .map(|n| n.attachments),
+    }
+    .ok_or_else(|| not_found(target, &id))
+}
+
+/// Drops the reference and deletes the blob if nothing else uses it.
+#[command]
+pub fn remove_attachment(target: AttachmentTarget, id: String, hash: String) -> Result<(), String> {
+    update(target, &id, |attachments| {
+        let before = attachments.len();
+        attachments.retain(|a| a.hash != hash);
+        if attachments.len() == before {
+            return Err(format!("'{}' has no attachment '{}'", id, hash));
+        }
+        Ok(())
+    })?;
+    collect_garbage()?;
+    Ok(())
+}
+
+/// Writes a decrypted copy of an attachment to `path`.
+#[command]
+pub fn export_attachment(hash: String, path: String) -> Result<(), String> {
+    if !is_hash(&hash) {
+        return Err(format!("'{}' is not an attachment hash", hash));
+    }
+    let bytes = storage::read(&blob_file(&hash))?
+        .ok_or_else(|| format!("Attachment '{}' is missing", hash))?;
+    fs::write(&path, bytes).map_err(|e| format!("Failed to write attachment: {}", e))
+}
+
+fn referenced_hashes() -> Result<HashSet<String>, String> {
+    let tasks: Vec<Task> = schema::load(&schema::TASKS)?;
+    let events: Vec<Event> = schema::load(&schema::EVENTS)?;
+    let archived = archive::load_archive()?;
+    let notes = notes::load_notes()?;
+
+    let mut hashes: HashSet<String> = tasks
+        .iter()
+        .chain(archived.iter().map(|a| &a.task))
+        .flat_map(|t| &t.attachments)
+        .chain(events.iter().flat_map(|e| &e.attachments))
+        .chain(notes.iter().flat_map(|n| &n.attachments))
+        .map(|a| a.hash.clone())
+        .collect();
+    hashes.extend(journal::attachment_hashes()?);
+    Ok(hashes)
+}
+
+/// Deletes blobs that no task, archived task, event, note or undo step
+/// refers to.
+pub fn collect_garbage() -> Result<GarbageSummary, String> {
+    let referenced = referenced_hashes()?;
+
+    let mut summary = GarbageSummary { removed: 0, bytes: 0 };
+    for name in storage::dir_files(DIR)? {
+        let hash = name.trim_start_matches(DIR).trim_start_matches('/');
+        if referenced.contains(hash) {
+            continue;
+        }
+        let path = profiles::data_file(&name)?;
+        let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        fs::remove_file(&path).map_err(|e| format!("Failed to delete attachment: {}", e))?;
+        summary.removed += 1;
+        summary.bytes += size;
+    }
+    Ok(summary)
+}
+
+#[command]
+pub fn collect_attachment_garbage() -> Result<GarbageSummary, String> {
+    collect_garbage()
+}
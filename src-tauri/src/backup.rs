@@ -0,0 +1,479 @@
+// Backup & restore
+//
+// A backup is a zip archive with a manifest.json and every data file of the
+// active profile, including the files in data directories such as notes/. The
+// manifest records a SHA-256 checksum per file, so damaged or hand-edited
+// archives are rejected before anything is restored. Archives hold decrypted
+// data so they can be restored on a machine with another key.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::command;
+
+use crate::notes::{self, Note};
+use crate::schema::{self, Schema};
+use crate::{profiles, storage, Event, Task};
+
+const MANIFEST_NAME: &str = "manifest.json";
+const FORMAT_VERSION: u32 = 1;
+// Larger entries are rejected rather than read into memory
+const MAX_ENTRY_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Files included in a full backup: every data file except the local-only
+/// ones. Archives containing anything else are rejected.
+fn backup_files() -> impl Iterator<Item = &'static str> {
+    storage::DATA_FILES
+        .iter()
+        .copied()
+        .filter(|name| !storage::LOCAL_ONLY_FILES.contains(name))
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BackupEntry {
+    name: String,
+    size: u64,
+    sha256: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BackupManifest {
+    format_version: u32,
+    app_version: String,
+    profile: String,
+    created_at: String,
+    files: Vec<BackupEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RestoreMode {
+    Merge,
+    Replace,
+}
+
+#[derive(Serialize)]
+pub struct FileChange {
+    name: String,
+    // "create", "overwrite", "merge", "delete" or "keep"
+    action: String,
+    added: usize,
+    updated: usize,
+    removed: usize,
+    unchanged: usize,
+}
+
+#[derive(Serialize)]
+pub struct RestorePreview {
+    manifest: BackupManifest,
+    mode: RestoreMode,
+    changes: Vec<FileChange>,
+}
+
+// Tasks and events are merged record by record instead of file by file.
+trait Record: Serialize + DeserializeOwned {
+    fn id(&self) -> &str;
+    fn updated_at(&self) -> Option<&str>;
+}
+
+impl Record for Task {
+    fn id(&self) -> &str {
+        &self.id
+    }
+    fn updated_at(&self) -> Option<&str> {
+        self.updated_at.as_deref()
+    }
+}
+
+impl Record for Event {
+    fn id(&self) -> &str {
+        &self.id
+    }
+    fn updated_at(&self) -> Option<&str> {
+        self.updated_at.as_deref()
+    }
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+fn is_newer(incoming: Option<&str>, local: Option<&str>) -> bool {
+    let parse = |s: Option<&str>| s.and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok());
+    match (parse(incoming), parse(local)) {
+        (Some(incoming), Some(local)) => incoming > local,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+fn same_record<T: Record>(a: &T, b: &T) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+fn is_backup_name(name: &str) -> bool {
+    if backup_files().any(|file| file == name) {
+        return true;
+    }
+    // Files under a backup directory, without any way out of it
+    storage::DATA_DIRS.iter().any(|dir| {
+        name.strip_prefix(dir)
+            .and_then(|rest| rest.strip_prefix('/'))
+            .is_some_and(|rest| {
+                !rest.contains('\\') && rest.split('/').all(|part| !part.is_empty() && part != "." && part != "..")
+            })
+    })
+}
+
+// Every file a full backup covers: the fixed files, the files currently in
+// the backup directories and any further ones `extra` brings along.
+fn full_backup_names<'a>(extra: impl Iterator<Item = &'a String>) -> Result<Vec<String>, String> {
+    let mut names: Vec<String> = backup_files().map(String::from).collect();
+    for dir in storage::DATA_DIRS {
+        names.extend(storage::dir_files(dir)?);
+    }
+    for name in extra {
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+    Ok(names)
+}
+
+fn read_local(name: &str) -> Result<Option<Vec<u8>>, String> {
+    storage::read(name).map_err(|e| format!("Failed to read {}: {}", name, e))
+}
+
+/// Builds an archive of the given data files of the active profile.
+pub fn archive_bytes(names: &[&str]) -> Result<(BackupManifest, Vec<u8>), String> {
+    let mut files = Vec::new();
+    for name in names {
+        if let Some(bytes) = read_local(name)? {
+            files.push((name.to_string(), bytes));
+        }
+    }
+
+    let manifest = BackupManifest {
+        format_version: FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        profile: profiles::active_profile(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        files: files
+            .iter()
+            .map(|(name, bytes)| BackupEntry {
+                name: name.clone(),
+                size: bytes.len() as u64,
+                sha256: sha256_hex(bytes),
+            })
+            .collect(),
+    };
+    let manifest_json = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::FileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    let entries = std::iter::once((MANIFEST_NAME.to_string(), manifest_json)).chain(files);
+    for (name, bytes) in entries {
+        zip.start_file(name.as_str(), options)
+            .map_err(|e| format!("Failed to add {} to backup: {}", name, e))?;
+        zip.write_all(&bytes)
+            .map_err(|e| format!("Failed to write {} to backup: {}", name, e))?;
+    }
+    let bytes = zip.finish().map_err(|e| format!("Failed to finish backup: {}", e))?.into_inner();
+
+    Ok((manifest, bytes))
+}
+
+/// Writes the given data files of the active profile into an archive at `path`.
+pub fn write_archive(path: &Path, names: &[&str]) -> Result<BackupManifest, String> {
+    let (manifest, bytes) = archive_bytes(names)?;
+    fs::write(path, bytes).map_err(|e| format!("Failed to write backup file: {}", e))?;
+    Ok(manifest)
+}
+
+#[command]
+pub fn export_backup(path: String) -> Result<BackupManifest, String> {
+    let names = full_backup_names(std::iter::empty())?;
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    write_archive(Path::new(&path), &names)
+}
+
+fn read_archive(path: &Path) -> Result<(BackupManifest, HashMap<String, Vec<u8>>), String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to open backup file: {}", e))?;
+    parse_archive(bytes)
+}
+
+fn parse_archive(bytes: Vec<u8>) -> Result<(BackupManifest, HashMap<String, Vec<u8>>), String> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))
+        .map_err(|e| format!("Backup is not a valid archive: {}", e))?;
+
+    let mut contents = HashMap::new();
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .map_err(|e| format!("Failed to read backup entry: {}", e))?;
+        let name = entry.name().to_string();
+        if name != MANIFEST_NAME && !is_backup_name(&name) {
+            return Err(format!("Backup contains unexpected file '{}'", name));
+        }
+
+        let too_large = || format!("'{}' in the backup is larger than {} MB", name, MAX_ENTRY_BYTES >> 20);
+        if entry.size() > MAX_ENTRY_BYTES {
+            return Err(too_large());
+        }
+        // The size in the header is not trusted
+        let mut bytes = Vec::new();
+        (&mut entry)
+            .take(MAX_ENTRY_BYTES + 1)
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("Failed to read {} from backup: {}", name, e))?;
+        if bytes.len() as u64 > MAX_ENTRY_BYTES {
+            return Err(too_large());
+        }
+        contents.insert(name, bytes);
+    }
+
+    let manifest_json = contents
+        .remove(MANIFEST_NAME)
+        .ok_or_else(|| "Backup has no manifest".to_string())?;
+    let manifest: BackupManifest = serde_json::from_slice(&manifest_json)
+        .map_err(|e| format!("Failed to deserialize manifest: {}", e))?;
+
+    if manifest.format_version > FORMAT_VERSION {
+        return Err(format!(
+            "Backup format version {} is newer than supported version {}",
+            manifest.format_version, FORMAT_VERSION
+        ));
+    }
+
+    let listed: HashSet<&str> = manifest.files.iter().map(|f| f.name.as_str()).collect();
+    if let Some(extra) = contents.keys().find(|name| !listed.contains(name.as_str())) {
+        return Err(format!("Backup file '{}' is not listed in the manifest", extra));
+    }
+    for entry in &manifest.files {
+        let bytes = contents
+            .get(&entry.name)
+            .ok_or_else(|| format!("Backup is missing '{}'", entry.name))?;
+        if bytes.len() as u64 != entry.size || sha256_hex(bytes) != entry.sha256 {
+            return Err(format!("Checksum mismatch for '{}'", entry.name));
+        }
+    }
+
+    Ok((manifest, contents))
+}
+
+fn plan_records<T: Record>(
+    schema: &Schema,
+    local: Option<&[u8]>,
+    incoming: &[u8],
+    mode: RestoreMode,
+) -> Result<(FileChange, Vec<u8>), String> {
+    let name = schema.file;
+    let incoming: Vec<T> = schema::decode(schema, incoming)
+        .map_err(|e| format!("Backup file {} is invalid: {}", name, e))?;
+    let local_records: Vec<T> = match local {
+        Some(bytes) => schema::decode(schema, bytes)?,
+        None => Vec::new(),
+    };
+
+    let mut change = FileChange {
+        name: name.to_string(),
+        action: String::new(),
+        added: 0,
+        updated: 0,
+        removed: 0,
+        unchanged: 0,
+    };
+
+    let incoming_ids: HashSet<&str> = incoming.iter().map(|r| r.id()).collect();
+    let mut local_by_id: HashMap<&str, &T> = HashMap::new();
+    for record in &local_records {
+        local_by_id.insert(record.id(), record);
+    }
+
+    let result: Vec<&T> = match mode {
+        RestoreMode::Replace => {
+            for record in &incoming {
+                match local_by_id.get(record.id()) {
+                    None => change.added += 1,
+                    Some(existing) if same_record(*existing, record) => change.unchanged += 1,
+                    Some(_) => change.updated += 1,
+                }
+            }
+            change.removed = local_records
+                .iter()
+                .filter(|r| !incoming_ids.contains(r.id()))
+                .count();
+            incoming.iter().collect()
+        }
+        RestoreMode::Merge => {
+            let incoming_by_id: HashMap<&str, &T> = incoming.iter().map(|r| (r.id(), r)).collect();
+            let mut merged = Vec::new();
+            for record in &local_records {
+                match incoming_by_id.get(record.id()) {
+                    Some(newer) if is_newer(newer.updated_at(), record.updated_at())
+                        && !same_record(*newer, record) =>
+                    {
+                        change.updated += 1;
+                        merged.push(*newer);
+                    }
+                    _ => {
+                        change.unchanged += 1;
+                        merged.push(record);
+                    }
+                }
+            }
+            for record in &incoming {
+                if !local_by_id.contains_key(record.id()) {
+                    change.added += 1;
+                    merged.push(record);
+                }
+            }
+            merged
+        }
+    };
+
+    change.action = if local.is_none() {
+        "create"
+    } else if change.added + change.updated + change.removed == 0 {
+        "keep"
+    } else if mode == RestoreMode::Merge {
+        "merge"
+    } else {
+        "overwrite"
+    }
+    .to_string();
+
+    Ok((change, schema::encode(schema, &result)?))
+}
+
+// Each change carries the bytes to write, or None when the file should be
+// left alone or deleted.
+type RestorePlan = Vec<(FileChange, Option<Vec<u8>>)>;
+
+fn plan_restore(
+    contents: &HashMap<String, Vec<u8>>,
+    mode: RestoreMode,
+    names: &[&str],
+) -> Result<RestorePlan, String> {
+    let mut plan = Vec::new();
+
+    // Merging keeps an existing note index, so files of notes only the backup
+    // knows about would be orphans
+    let kept_notes: Option<HashSet<String>> = match read_local(notes::NOTES.file)? {
+        Some(bytes) if mode == RestoreMode::Merge => Some(
+            schema::decode::<Note>(&notes::NOTES, &bytes)?
+                .into_iter()
+                .map(|note| note.id)
+                .collect(),
+        ),
+        _ => None,
+    };
+
+    for name in names {
+        let note_id = name.strip_prefix("notes/").and_then(|rest| rest.split('/').next());
+        if let (Some(kept), Some(id)) = (&kept_notes, note_id) {
+            if !kept.contains(id) {
+                continue;
+            }
+        }
+
+        let local = read_local(name)?;
+        let simple = |action: &str| FileChange {
+            name: name.to_string(),
+            action: action.to_string(),
+            added: 0,
+            updated: 0,
+            removed: 0,
+            unchanged: 0,
+        };
+
+        let incoming = match contents.get(*name) {
+            Some(incoming) => incoming,
+            None => {
+                if local.is_some() {
+                    if mode == RestoreMode::Replace {
+                        plan.push((simple("delete"), None));
+                    } else {
+                        plan.push((simple("keep"), None));
+                    }
+                }
+                continue;
+            }
+        };
+
+        let (change, bytes) = match *name {
+            n if n == schema::TASKS.file => plan_records::<Task>(&schema::TASKS, local.as_deref(), incoming, mode)?,
+            n if n == schema::EVENTS.file => plan_records::<Event>(&schema::EVENTS, local.as_deref(), incoming, mode)?,
+            _ => match &local {
+                None => (simple("create"), incoming.clone()),
+                Some(existing) if existing == incoming || mode == RestoreMode::Merge => {
+                    (simple("keep"), existing.clone())
+                }
+                Some(_) => (simple("overwrite"), incoming.clone()),
+            },
+        };
+
+        let bytes = if change.action == "keep" { None } else { Some(bytes) };
+        plan.push((change, bytes));
+    }
+
+    Ok(plan)
+}
+
+#[command]
+pub fn preview_backup(path: String, mode: RestoreMode) -> Result<RestorePreview, String> {
+    let (manifest, contents) = read_archive(Path::new(&path))?;
+    let names = full_backup_names(contents.keys())?;
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let changes = plan_restore(&contents, mode, &names)?
+        .into_iter()
+        .map(|(change, _)| change)
+        .collect();
+
+    Ok(RestorePreview { manifest, mode, changes })
+}
+
+/// Restores an archive into the active profile. Only the files in `names` are
+/// touched, so a partial archive never wipes unrelated data in replace mode.
+pub fn restore_archive(bytes: Vec<u8>, mode: RestoreMode, names: &[&str]) -> Result<RestorePreview, String> {
+    let (manifest, contents) = parse_archive(bytes)?;
+    restore_contents(manifest, &contents, mode, names)
+}
+
+fn restore_contents(
+    manifest: BackupManifest,
+    contents: &HashMap<String, Vec<u8>>,
+    mode: RestoreMode,
+    names: &[&str],
+) -> Result<RestorePreview, String> {
+    let plan = plan_restore(contents, mode, names)?;
+
+    let mut changes = Vec::new();
+    for (change, bytes) in plan {
+        match bytes {
+            Some(bytes) => storage::write(&change.name, &bytes)
+                .map_err(|e| format!("Failed to restore {}: {}", change.name, e))?,
+            None if change.action == "delete" => fs::remove_file(profiles::data_file(&change.name)?)
+                .map_err(|e| format!("Failed to remove {}: {}", change.name, e))?,
+            None => {}
+        }
+        changes.push(change);
+    }
+
+    Ok(RestorePreview { manifest, mode, changes })
+}
+
+#[command]
+pub fn import_backup(path: String, mode: RestoreMode) -> Result<RestorePreview, String> {
+    let (manifest, contents) = read_archive(Path::new(&path))?;
+    let names = full_backup_names(contents.keys())?;
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    restore_contents(manifest, &contents, mode, &names)
+}
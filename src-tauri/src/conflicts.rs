@@ -0,0 +1,254 @@
+// Event conflicts
+//
+// Local events and cached Google Calendar events are normalized to intervals
+// with a UTC offset (see timezones) and swept in start order to find the
+// ones that overlap. A conflict where one event lies entirely within the
+// other is reported as a double booking. All-day events usually mark days
+// rather than commitments, so they are left out unless asked for.
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate};
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+use crate::tasks::{parse_bound, parse_task_date};
+use crate::timezones;
+use crate::{CalendarEvent, Event};
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EventSource {
+    Local,
+    Google,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Interval {
+    pub source: EventSource,
+    // Local event id; Google events are cached without one
+    pub id: Option<String>,
+    pub title: String,
+    pub start: DateTime<FixedOffset>,
+    // Exclusive
+    pub end: DateTime<FixedOffset>,
+    pub all_day: bool,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    Overlap,
+    DoubleBooking,
+}
+
+#[derive(Serialize)]
+pub struct Conflict {
+    kind: ConflictKind,
+    first: Interval,
+    second: Interval,
+    overlap_start: DateTime<FixedOffset>,
+    overlap_end: DateTime<FixedOffset>,
+    overlap_minutes: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ConflictOptions {
+    // Inclusive, YYYY-MM-DD
+    from: Option<String>,
+    to: Option<String>,
+    include_all_day: bool,
+    include_google: bool,
+}
+
+impl Default for ConflictOptions {
+    fn default() -> Self {
+        ConflictOptions {
+            from: None,
+            to: None,
+            include_all_day: false,
+            include_google: true,
+        }
+    }
+}
+
+/// The interval a local event occupies, in its own zone.
+pub fn local_interval(event: &Event) -> Option<Interval> {
+    let (start, end, all_day) = timezones::event_span(event)?;
+    Some(Interval {
+        source: EventSource::Local,
+        id: Some(event.id.clone()),
+        title: event.title.clone(),
+        start: start.fixed_offset(),
+        end: end.fixed_offset(),
+        all_day,
+    })
+}
+
+fn midnight(date: NaiveDate) -> Option<DateTime<FixedOffset>> {
+    timezones::midnight(timezones::user_zone(), date).map(|d| d.fixed_offset())
+}
+
+/// The interval of a Google event. Timed events carry their own offset;
+/// all-day ones have dates with an exclusive end, taken in the user's zone.
+pub fn google_interval(event: &CalendarEvent) -> Option<Interval> {
+    let start = event.start.as_deref()?;
+    let end = event.end.as_deref().unwrap_or(start);
+
+    let (start, end, all_day) = match DateTime::parse_from_rfc3339(start) {
+        Ok(start) => (start, DateTime::parse_from_rfc3339(end).ok()?.max(start), false),
+        Err(_) => {
+            let start_date = parse_task_date(start)?;
+            let end_date = parse_task_date(end).filter(|d| *d > start_date).unwrap_or(start_date + Duration::days(1));
+            (midnight(start_date)?, midnight(end_date)?, true)
+        }
+    };
+
+    Some(Interval {
+        source: EventSource::Google,
+        id: None,
+        title: event.summary.clone(),
+        start,
+        end,
+        all_day,
+    })
+}
+
+/// Every local and, optionally, Google interval touching the given dates.
+pub fn intervals(
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    include_google: bool,
+    include_all_day: bool,
+) -> Result<Vec<Interval>, String> {
+    let mut intervals: Vec<Interval> = crate::load_local_events()?.iter().filter_map(local_interval).collect();
+    if include_google {
+        intervals.extend(crate::load_google_events_cache()?.iter().filter_map(google_interval));
+    }
+
+    let from = from.and_then(midnight);
+    let to = to.and_then(|to| midnight(to + Duration::days(1)));
+    intervals.retain(|i| {
+        (include_all_day || !i.all_day)
+            && i.end > i.start
+            && from.is_none_or(|from| i.end > from)
+            && to.is_none_or(|to| i.start < to)
+    });
+    intervals.sort_by_key(|i| (i.start, i.end));
+    Ok(intervals)
+}
+
+fn conflict(a: &Interval, b: &Interval) -> Option<Conflict> {
+    let overlap_start = a.start.max(b.start);
+    let overlap_end = a.end.min(b.end);
+    if overlap_start >= overlap_end {
+        return None;
+    }
+    let contains = |outer: &Interval, inner: &Interval| outer.start <= inner.start && inner.end <= outer.end;
+    Some(Conflict {
+        kind: if contains(a, b) || contains(b, a) {
+            ConflictKind::DoubleBooking
+        } else {
+            ConflictKind::Overlap
+        },
+        first: a.clone(),
+        second: b.clone(),
+        overlap_start,
+        overlap_end,
+        overlap_minutes: (overlap_end - overlap_start).num_minutes(),
+    })
+}
+
+// The same Google event cached twice
+fn same_event(a: &Interval, b: &Interval) -> bool {
+    a.source != EventSource::Local
+        && b.source != EventSource::Local
+        && a.title == b.title
+        && a.start == b.start
+        && a.end == b.end
+}
+
+/// Overlapping pairs among intervals sorted by start.
+fn sweep(intervals: &[Interval]) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    let mut active: Vec<&Interval> = Vec::new();
+    for interval in intervals {
+        active.retain(|a| a.end > interval.start);
+        conflicts.extend(
+            active
+                .iter()
+                .filter(|a| !same_event(a, interval))
+                .filter_map(|a| conflict(a, interval)),
+        );
+        active.push(interval);
+    }
+    conflicts
+}
+
+/// Overlaps and double bookings between events in a date range.
+#[command]
+pub fn find_event_conflicts(options: Option<ConflictOptions>) -> Result<Vec<Conflict>, String> {
+    let options = options.unwrap_or_default();
+    let (from, to) = (parse_bound(&options.from)?, parse_bound(&options.to)?);
+    let intervals = intervals(from, to, options.include_google, options.include_all_day)?;
+    Ok(sweep(&intervals))
+}
+
+/// Conflicts a local event would have with the existing ones, for warning
+/// before it is saved. The event itself is skipped when it already exists.
+#[command]
+pub fn check_event_conflicts(event: Event, include_all_day: Option<bool>) -> Result<Vec<Conflict>, String> {
+    let include_all_day = include_all_day.unwrap_or(false);
+    let Some(candidate) = local_interval(&event).filter(|i| include_all_day || !i.all_day) else {
+        return Ok(Vec::new());
+    };
+
+    let from = candidate.start.date_naive();
+    let to = candidate.end.date_naive();
+    let mut conflicts: Vec<Conflict> = intervals(Some(from), Some(to), true, include_all_day)?
+        .iter()
+        .filter(|i| i.id.as_deref() != Some(event.id.as_str()))
+        .filter_map(|existing| conflict(&candidate, existing))
+        .collect();
+    conflicts.sort_by_key(|c| c.overlap_start);
+    Ok(conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interval(source: EventSource, title: &str, start: &str, end: &str) -> Interval {
+        Interval {
+            source,
+            id: None,
+            title: title.to_string(),
+            start: DateTime::parse_from_rfc3339(&format!("2024-05-10T{}:00+02:00", start)).unwrap(),
+            end: DateTime::parse_from_rfc3339(&format!("2024-05-10T{}:00+02:00", end)).unwrap(),
+            all_day: false,
+        }
+    }
+
+    #[test]
+    fn sweeps_overlaps_and_double_bookings() {
+        let intervals = vec![
+            interval(EventSource::Local, "Planning", "09:00", "11:00"),
+            interval(EventSource::Local, "Call", "09:30", "10:30"),
+            interval(EventSource::Local, "Review", "10:00", "12:00"),
+            // Touches Review without overlapping it
+            interval(EventSource::Local, "Lunch", "12:00", "13:00"),
+            // Cached twice
+            interval(EventSource::Google, "Demo", "14:00", "15:00"),
+            interval(EventSource::Google, "Demo", "14:00", "15:00"),
+        ];
+
+        let found = sweep(&intervals);
+        let conflicts: Vec<(&str, &str, ConflictKind, i64)> = found
+            .iter()
+            .map(|c| (c.first.title.as_str(), c.second.title.as_str(), c.kind, c.overlap_minutes))
+            .collect();
+        assert_eq!(conflicts.len(), 3);
+        assert!(conflicts.contains(&("Planning", "Call", ConflictKind::DoubleBooking, 60)));
+        assert!(conflicts.contains(&("Planning", "Review", ConflictKind::Overlap, 60)));
+        assert!(conflicts.contains(&("Call", "Review", ConflictKind::Overlap, 30)));
+    }
+}
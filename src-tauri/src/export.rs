@@ -0,0 +1,228 @@
+// Task and event export
+//
+// Renders the local tasks and events as CSV, a Markdown checklist or pretty
+// JSON for pasting into status reports. Tasks use the same filter as
+// query_tasks; events are filtered by their start date.
+
+use std::collections::BTreeMap;
+use std::fs;
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+use crate::tasks::{self, TaskFilter};
+use crate::{Event, Task};
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Markdown,
+    Json,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ExportOptions {
+    include_tasks: bool,
+    include_events: bool,
+    tasks: TaskFilter,
+    // Inclusive, YYYY-MM-DD; applied to the event start date
+    events_from: Option<String>,
+    events_to: Option<String>,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            include_tasks: true,
+            include_events: true,
+            tasks: TaskFilter::default(),
+            events_from: None,
+            events_to: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ExportSummary {
+    tasks: usize,
+    events: usize,
+}
+
+#[derive(Serialize)]
+struct JsonExport<'a> {
+    tasks: &'a [Task],
+    events: &'a [Event],
+}
+
+fn select_events(events: Vec<Event>, options: &ExportOptions) -> Result<Vec<Event>, String> {
+    let from = tasks::parse_bound(&options.events_from)?;
+    let to = tasks::parse_bound(&options.events_to)?;
+
+    let mut events: Vec<Event> = events
+        .into_iter()
+        .filter(|e| {
+            if from.is_none() && to.is_none() {
+                return true;
+            }
+            match e.date_start.as_deref().and_then(tasks::parse_task_date) {
+                Some(date) => from.is_none_or(|from| date >= from) && to.is_none_or(|to| date <= to),
+                None => false,
+            }
+        })
+        .collect();
+    events.sort_by(|a, b| (&a.date_start, &a.time_start).cmp(&(&b.date_start, &b.time_start)));
+    Ok(events)
+}
+
+fn render_csv(tasks: &[Task], events: &[Event]) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let write_err = |e: csv::Error| format!("Failed to write CSV: {}", e);
+
+    writer
+        .write_record([
+            "type", "title", "date", "end_date", "time", "project", "completed", "completed_on", "priority", "tags",
+            "location", "description",
+        ])
+        .map_err(write_err)?;
+    for task in tasks {
+        let priority = serde_json::to_value(task.priority)
+            .ok()
+            .and_then(|v| v.as_str().map(String::from))
+            .unwrap_or_default();
+        writer
+            .write_record([
+                "task",
+                &task.title,
+                &task.date,
+                "",
+                "",
+                &task.project,
+                if task.completed { "yes" } else { "no" },
+                task.completed_on.as_deref().unwrap_or(""),
+                &priority,
+                &task.tags.join(", "),
+                "",
+                &task.description,
+            ])
+            .map_err(write_err)?;
+    }
+    for event in events {
+        let time = match (&event.time_start, &event.time_end) {
+            (Some(start), Some(end)) => format!("{}-{}", start, end),
+            (Some(start), None) => start.clone(),
+            _ => String::new(),
+        };
+        writer
+            .write_record([
+                "event",
+                &event.title,
+                event.date_start.as_deref().unwrap_or(""),
+                event.date_end.as_deref().unwrap_or(""),
+                &time,
+                "",
+                "",
+                "",
+                "",
+                "",
+                event.location.as_deref().unwrap_or(""),
+                event.description.as_deref().unwrap_or(""),
+            ])
+            .map_err(write_err)?;
+    }
+
+    let bytes = writer.into_inner().map_err(|e| format!("Failed to write CSV: {}", e))?;
+    String::from_utf8(bytes).map_err(|e| format!("Failed to write CSV: {}", e))
+}
+
+fn render_markdown(tasks: &[Task], events: &[Event], options: &ExportOptions) -> String {
+    let mut out = String::new();
+
+    if options.include_tasks {
+        out.push_str("# Tasks\n");
+        // Grouped by project, tasks without one last
+        let mut projects: BTreeMap<(bool, &str), Vec<&Task>> = BTreeMap::new();
+        for task in tasks {
+            let project = task.project.trim();
+            projects.entry((project.is_empty(), project)).or_default().push(task);
+        }
+        if projects.is_empty() {
+            out.push_str("\n_No tasks_\n");
+        }
+        for ((no_project, project), tasks) in projects {
+            out.push_str(&format!("\n## {}\n\n", if no_project { "No project" } else { project }));
+            for task in tasks {
+                let check = if task.completed { "x" } else { " " };
+                out.push_str(&format!("- [{}] {}", check, task.title.trim()));
+                if !task.date.is_empty() {
+                    out.push_str(&format!(" (due {})", task.date.get(..10).unwrap_or(&task.date)));
+                }
+                if !task.tags.is_empty() {
+                    let tags: Vec<String> = task.tags.iter().map(|t| format!("`{}`", t)).collect();
+                    out.push_str(&format!(" {}", tags.join(" ")));
+                }
+                out.push('\n');
+            }
+        }
+    }
+
+    if options.include_events {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str("# Events\n\n");
+        if events.is_empty() {
+            out.push_str("_No events_\n");
+        }
+        for event in events {
+            let mut when = event.date_start.clone().unwrap_or_default();
+            if let Some(end) = event.date_end.as_ref().filter(|end| Some(*end) != event.date_start.as_ref()) {
+                when.push_str(&format!(" \u{2013} {}", end));
+            }
+            if let Some(start) = &event.time_start {
+                when.push_str(&format!(" {}", start));
+                if let Some(end) = &event.time_end {
+                    when.push_str(&format!("\u{2013}{}", end));
+                }
+            }
+            out.push_str(&format!("- {} **{}**", when.trim(), event.title.trim()));
+            if let Some(location) = event.location.as_ref().filter(|l| !l.trim().is_empty()) {
+                out.push_str(&format!(" @ {}", location.trim()));
+            }
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+#[command]
+pub fn export_report(path: String, format: ExportFormat, options: Option<ExportOptions>) -> Result<ExportSummary, String> {
+    let options = options.unwrap_or_default();
+
+    let mut tasks = if options.include_tasks {
+        tasks::filter_tasks(crate::load_local_tasks()?, &options.tasks)?
+    } else {
+        Vec::new()
+    };
+    // Dated tasks first
+    tasks.sort_by_key(|t| (tasks::parse_task_date(&t.date).is_none(), t.date.clone(), t.title.to_lowercase()));
+    let events = if options.include_events {
+        select_events(crate::load_local_events()?, &options)?
+    } else {
+        Vec::new()
+    };
+
+    let content = match format {
+        ExportFormat::Csv => render_csv(&tasks, &events)?,
+        ExportFormat::Markdown => render_markdown(&tasks, &events, &options),
+        ExportFormat::Json => serde_json::to_string_pretty(&JsonExport { tasks: &tasks, events: &events })
+            .map_err(|e| format!("Failed to serialize export: {}", e))?,
+    };
+    fs::write(&path, content).map_err(|e| format!("Failed to write export: {}", e))?;
+
+    Ok(ExportSummary {
+        tasks: tasks.len(),
+        events: events.len(),
+    })
+}
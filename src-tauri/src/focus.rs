@@ -0,0 +1,384 @@
+// Focus sessions
+//
+// A pomodoro timer that lives in the backend, so it keeps running while the
+// window reloads; the UI asks for the current state with get_focus_state and
+// follows "focus-tick" and "focus-phase-changed" events afterwards. Finished
+// work phases are recorded as sessions against the task they were started for.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{command, Manager};
+
+use crate::schema::{self, Schema};
+use crate::storage;
+
+const SETTINGS_FILE: &str = "focus_settings.json";
+const TICK_INTERVAL_MS: u64 = 1000;
+// Stopped or skipped work shorter than this is not recorded
+const MIN_RECORDED_SECS: i64 = 60;
+
+pub const SESSIONS: Schema = Schema {
+    file: "focus_sessions.json",
+    version: 1,
+    migrations: &[schema::wrap_items],
+};
+
+static TIMER: Mutex<Option<Timer>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct FocusSettings {
+    work_minutes: u32,
+    short_break_minutes: u32,
+    long_break_minutes: u32,
+    // Work sessions before a long break
+    long_break_every: u32,
+    // Start the next phase right away instead of waiting for start_focus
+    auto_start: bool,
+}
+
+impl Default for FocusSettings {
+    fn default() -> Self {
+        FocusSettings {
+            work_minutes: 25,
+            short_break_minutes: 5,
+            long_break_minutes: 15,
+            long_break_every: 4,
+            auto_start: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Work,
+    ShortBreak,
+    LongBreak,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Running,
+    Paused,
+}
+
+struct Timer {
+    phase: Phase,
+    task_id: Option<String>,
+    phase_secs: i64,
+    // Time spent in the phase before the current run
+    elapsed_secs: i64,
+    running_since: Option<DateTime<Utc>>,
+    phase_started_at: DateTime<Utc>,
+    // Work phases finished since the last long break
+    completed_work: u32,
+}
+
+#[derive(Serialize, Clone)]
+pub struct FocusState {
+    phase: Phase,
+    status: Status,
+    task_id: Option<String>,
+    phase_seconds: i64,
+    remaining_seconds: i64,
+    completed_work: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct FocusSession {
+    pub id: String,
+    pub task_id: Option<String>,
+    pub started_at: String,
+    pub ended_at: String,
+    pub focus_seconds: i64,
+    // Stopped or skipped before the work phase ran out
+    pub interrupted: bool,
+}
+
+#[derive(Serialize)]
+pub struct TaskFocus {
+    task_id: Option<String>,
+    sessions: usize,
+    focus_seconds: i64,
+}
+
+#[derive(Serialize, Clone)]
+struct PhaseChange {
+    previous: Phase,
+    state: FocusState,
+    session: Option<FocusSession>,
+}
+
+fn load_settings() -> Result<FocusSettings, String> {
+    let json = match storage::read_to_string(SETTINGS_FILE)
+        .map_err(|e| format!("Failed to read focus settings: {}", e))?
+    {
+        Some(json) => json,
+        None => return Ok(FocusSettings::default()),
+    };
+
+    serde_json::from_str(&json).map_err(|e| format!("Failed to deserialize focus settings: {}", e))
+}
+
+fn phase_secs(settings: &FocusSettings, phase: Phase) -> i64 {
+    let minutes = match phase {
+        Phase::Work => settings.work_minutes,
+        Phase::ShortBreak => settings.short_break_minutes,
+        Phase::LongBreak => settings.long_break_minutes,
+    };
+    minutes.max(1) as i64 * 60
+}
+
+impl Timer {
+    fn new(phase: Phase, task_id: Option<String>, settings: &FocusSettings, completed_work: u32, running: bool) -> Timer {
+        let now = Utc::now();
+        Timer {
+            phase,
+            task_id,
+            phase_secs: phase_secs(settings, phase),
+            elapsed_secs: 0,
+            running_since: if running { Some(now) } else { None },
+            phase_started_at: now,
+            completed_work,
+        }
+    }
+
+    fn elapsed(&self, now: DateTime<Utc>) -> i64 {
+        let running = self.running_since.map_or(0, |since| (now - since).num_seconds());
+        (self.elapsed_secs + running).min(self.phase_secs)
+    }
+
+    fn state(&self, now: DateTime<Utc>) -> FocusState {
+        FocusState {
+            phase: self.phase,
+            status: if self.running_since.is_some() { Status::Running } else { Status::Paused },
+            task_id: self.task_id.clone(),
+            phase_seconds: self.phase_secs,
+            remaining_seconds: self.phase_secs - self.elapsed(now),
+            completed_work: self.completed_work,
+        }
+    }
+
+    fn pause(&mut self, now: DateTime<Utc>) {
+        self.elapsed_secs = self.elapsed(now);
+        self.running_since = None;
+    }
+
+    // Records the work done in the current phase, if it was a work phase.
+    fn record(&self, now: DateTime<Utc>, interrupted: bool) -> Result<Option<FocusSession>, String> {
+        let focus_seconds = self.elapsed(now);
+        if self.phase != Phase::Work || (interrupted && focus_seconds < MIN_RECORDED_SECS) {
+            return Ok(None);
+        }
+
+        let session = FocusSession {
+            id: uuid::Uuid::new_v4().to_string(),
+            task_id: self.task_id.clone(),
+            started_at: self.phase_started_at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            ended_at: now.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            focus_seconds,
+            interrupted,
+        };
+        let mut sessions: Vec<FocusSession> = schema::load(&SESSIONS)?;
+        sessions.push(session.clone());
+        schema::save(&SESSIONS, &sessions)?;
+        Ok(Some(session))
+    }
+
+    // Moves on to the phase after this one. Returns the new timer.
+    fn next(&self, settings: &FocusSettings, finished: bool) -> Timer {
+        match self.phase {
+            Phase::Work => {
+                let completed_work = self.completed_work + finished as u32;
+                let phase = if completed_work >= settings.long_break_every.max(1) {
+                    Phase::LongBreak
+                } else {
+                    Phase::ShortBreak
+                };
+                Timer::new(phase, self.task_id.clone(), settings, completed_work, settings.auto_start)
+            }
+            Phase::LongBreak => Timer::new(Phase::Work, self.task_id.clone(), settings, 0, settings.auto_start),
+            Phase::ShortBreak => {
+                Timer::new(Phase::Work, self.task_id.clone(), settings, self.completed_work, settings.auto_start)
+            }
+        }
+    }
+}
+
+fn lock() -> Result<std::sync::MutexGuard<'static, Option<Timer>>, String> {
+    TIMER.lock().map_err(|_| "Focus timer lock poisoned".to_string())
+}
+
+// Ends the current phase and starts the next one. The timer moves on even
+// when the finished phase can't be recorded or the settings can't be read,
+// so a storage failure never leaves it stuck at zero.
+fn advance(timer: &mut Option<Timer>, finished: bool) -> Option<PhaseChange> {
+    let current = timer.take()?;
+
+    let now = Utc::now();
+    let settings = load_settings().unwrap_or_else(|e| {
+        eprintln!("{}; using default focus settings", e);
+        FocusSettings::default()
+    });
+    let next = current.next(&settings, finished);
+    let state = next.state(now);
+    *timer = Some(next);
+
+    let session = current.record(now, !finished).unwrap_or_else(|e| {
+        eprintln!("Failed to record focus session: {}", e);
+        None
+    });
+    Some(PhaseChange {
+        previous: current.phase,
+        state,
+        session,
+    })
+}
+
+fn emit_phase_change(app_handle: &tauri::AppHandle, change: PhaseChange) {
+    if let Err(e) = app_handle.emit_all("focus-phase-changed", change) {
+        eprintln!("Failed to emit focus-phase-changed event: {}", e);
+    }
+}
+
+/// Emits a tick every second while the timer runs and moves on to the next
+/// phase when the current one runs out.
+pub fn start_ticker(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_millis(TICK_INTERVAL_MS)).await;
+
+            let (tick, change) = {
+                let mut timer = match lock() {
+                    Ok(timer) => timer,
+                    Err(_) => continue,
+                };
+                let state = match timer.as_ref().filter(|t| t.running_since.is_some()) {
+                    Some(t) => t.state(Utc::now()),
+                    None => continue,
+                };
+                let change = if state.remaining_seconds <= 0 {
+                    advance(&mut timer, true)
+                } else {
+                    None
+                };
+                (state, change)
+            };
+
+            if let Err(e) = app_handle.emit_all("focus-tick", tick) {
+                eprintln!("Failed to emit focus-tick event: {}", e);
+            }
+            if let Some(change) = change {
+                emit_phase_change(&app_handle, change);
+            }
+        }
+    });
+}
+
+#[command]
+pub fn get_focus_settings() -> Result<FocusSettings, String> {
+    load_settings()
+}
+
+#[command]
+pub fn save_focus_settings(settings: FocusSettings) -> Result<(), String> {
+    let json = serde_json::to_string(&settings)
+        .map_err(|e| format!("Failed to serialize focus settings: {}", e))?;
+    storage::write(SETTINGS_FILE, json.as_bytes())
+        .map_err(|e| format!("Failed to write focus settings: {}", e))
+}
+
+#[command]
+pub fn get_focus_state() -> Result<Option<FocusState>, String> {
+    Ok(lock()?.as_ref().map(|t| t.state(Utc::now())))
+}
+
+/// Starts a work phase, or resumes the paused timer. Passing a task id while
+/// a timer exists switches the task it counts towards.
+#[command]
+pub fn start_focus(task_id: Option<String>) -> Result<FocusState, String> {
+    let mut timer = lock()?;
+    let now = Utc::now();
+    match timer.as_mut() {
+        Some(current) => {
+            if task_id.is_some() {
+                current.task_id = task_id;
+            }
+            if current.running_since.is_none() {
+                current.running_since = Some(now);
+            }
+        }
+        None => {
+            *timer = Some(Timer::new(Phase::Work, task_id, &load_settings()?, 0, true));
+        }
+    }
+    timer
+        .as_ref()
+        .map(|t| t.state(now))
+        .ok_or_else(|| "Focus timer is not running".to_string())
+}
+
+#[command]
+pub fn pause_focus() -> Result<FocusState, String> {
+    let mut timer = lock()?;
+    let current = timer.as_mut().ok_or_else(|| "Focus timer is not running".to_string())?;
+    let now = Utc::now();
+    current.pause(now);
+    Ok(current.state(now))
+}
+
+/// Stops the timer. Work done so far in a work phase is recorded as an
+/// interrupted session.
+#[command]
+pub fn stop_focus() -> Result<Option<FocusSession>, String> {
+    let mut timer = lock()?;
+    let session = match timer.as_ref() {
+        Some(current) => current.record(Utc::now(), true)?,
+        None => None,
+    };
+    *timer = None;
+    Ok(session)
+}
+
+#[command]
+pub fn skip_focus(app_handle: tauri::AppHandle) -> Result<FocusState, String> {
+    let change = advance(&mut *lock()?, false).ok_or_else(|| "Focus timer is not running".to_string())?;
+    let state = change.state.clone();
+    emit_phase_change(&app_handle, change);
+    Ok(state)
+}
+
+#[command]
+pub fn list_focus_sessions(task_id: Option<String>) -> Result<Vec<FocusSession>, String> {
+    let sessions: Vec<FocusSession> = schema::load(&SESSIONS)?;
+    Ok(sessions
+        .into_iter()
+        .filter(|s| task_id.is_none() || s.task_id == task_id)
+        .collect())
+}
+
+/// Total focus time per task. Sessions without a task are grouped under a
+/// null task id.
+#[command]
+pub fn get_focus_time(task_id: Option<String>) -> Result<Vec<TaskFocus>, String> {
+    let mut totals: BTreeMap<Option<String>, (usize, i64)> = BTreeMap::new();
+    for session in list_focus_sessions(task_id)? {
+        let entry = totals.entry(session.task_id).or_default();
+        entry.0 += 1;
+        entry.1 += session.focus_seconds;
+    }
+
+    Ok(totals
+        .into_iter()
+        .map(|(task_id, (sessions, focus_seconds))| TaskFocus {
+            task_id,
+            sessions,
+            focus_seconds,
+        })
+        .collect())
+}
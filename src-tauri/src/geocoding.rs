@@ -0,0 +1,444 @@
+// Geocoding
+//
+// Keeps an event's location and coordinates consistent when it is saved: a
+// changed address is resolved into coordinates, changed coordinates into an
+// address. Lookups go through a Provider, either a Nominatim-compatible HTTP
+// service (the public one by default, or any self-hosted or mock server via
+// base_url) or a fixed list of places from the settings for working offline.
+// Results, including misses, are cached in geocoding_cache.json.
+//
+// Resolving on save is off until the user turns it on, since it sends event
+// addresses to the provider. Lookups during a save share a time budget, so a
+// slow or unreachable provider delays the save by a few seconds at most.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+use crate::{schema, storage, Event};
+
+const SETTINGS_FILE: &str = "geocoding_settings.json";
+const CACHE_FILE: &str = "geocoding_cache.json";
+const NOMINATIM_URL: &str = "https://nominatim.openstreetmap.org";
+const USER_AGENT: &str = "Daspberry";
+// Nominatim's usage policy allows one request per second
+const MIN_REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// Total time a save may spend on lookups; the rest are skipped
+const SAVE_LOOKUP_BUDGET: Duration = Duration::from_secs(5);
+// Mock places this close to the coordinates count as a match
+const MOCK_MATCH_METERS: f64 = 250.0;
+
+static LAST_REQUEST: Mutex<Option<Instant>> = Mutex::new(None);
+
+type Lookup<'a, T> = Pin<Box<dyn Future<Output = Result<Option<T>, String>> + Send + 'a>>;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    Nominatim,
+    Mock,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Place {
+    pub address: String,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct GeocodingSettings {
+    // Resolve locations when events are saved
+    enabled: bool,
+    provider: ProviderKind,
+    base_url: String,
+    // Sent to Nominatim so the operator can get in touch, as its policy asks
+    email: Option<String>,
+    // Places the mock provider knows about
+    mock_places: Vec<Place>,
+}
+
+impl Default for GeocodingSettings {
+    fn default() -> Self {
+        GeocodingSettings {
+            enabled: false,
+            provider: ProviderKind::Nominatim,
+            base_url: NOMINATIM_URL.to_string(),
+            email: None,
+            mock_places: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct Cache {
+    // Normalized address -> place, None when nothing was found
+    forward: BTreeMap<String, Option<Place>>,
+    // "lat,lon" rounded to about a meter -> address
+    reverse: BTreeMap<String, Option<String>>,
+}
+
+pub trait Provider: Send + Sync {
+    fn search<'a>(&'a self, address: &'a str) -> Lookup<'a, Place>;
+    fn reverse<'a>(&'a self, latitude: f64, longitude: f64) -> Lookup<'a, String>;
+}
+
+struct Nominatim {
+    client: reqwest::Client,
+    base_url: String,
+    email: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NominatimPlace {
+    lat: String,
+    lon: String,
+    display_name: String,
+}
+
+#[derive(Deserialize)]
+struct NominatimReverse {
+    display_name: Option<String>,
+    // Set instead of display_name when there is nothing at the coordinates
+    error: Option<String>,
+}
+
+impl Nominatim {
+    // Waits until the previous request is at least MIN_REQUEST_INTERVAL old.
+    async fn throttle() -> Result<(), String> {
+        let wait = {
+            let mut last = LAST_REQUEST
+                .lock()
+                .map_err(|_| "Geocoding throttle lock poisoned".to_string())?;
+            let now = Instant::now();
+            let next = last.map_or(now, |at| (at + MIN_REQUEST_INTERVAL).max(now));
+            *last = Some(next);
+            next - now
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        Ok(())
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<T, String> {
+        Self::throttle().await?;
+        let mut query = query.to_vec();
+        query.push(("format", "jsonv2".to_string()));
+        if let Some(email) = &self.email {
+            query.push(("email", email.clone()));
+        }
+
+        let url = format!("{}/{}", self.base_url.trim_end_matches('/'), path);
+        self.client
+            .get(&url)
+            .header(reqwest::header::USER_AGENT, USER_AGENT)
+            .query(&query)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Geocoding request failed: {}", e))?
+            .json::<T>()
+            .await
+            .map_err(|e| format!("Failed to parse geocoding response: {}", e))
+    }
+}
+
+impl Provider for Nominatim {
+    fn search<'a>(&'a self, address: &'a str) -> Lookup<'a, Place> {
+        Box::pin(async move {
+            let places: Vec<NominatimPlace> = self
+                .get("search", &[("q", address.to_string()), ("limit", "1".to_string())])
+                .await?;
+            Ok(places.into_iter().next().and_then(|p| {
+                Some(Place {
+                    latitude: p.lat.parse().ok()?,
+                    longitude: p.lon.parse().ok()?,
+                    address: p.display_name,
+                })
+            }))
+        })
+    }
+
+    fn reverse<'a>(&'a self, latitude: f64, longitude: f64) -> Lookup<'a, String> {
+        Box::pin(async move {
+            let place: NominatimReverse = self
+                .get("reverse", &[("lat", latitude.to_string()), ("lon", longitude.to_string())])
+                .await?;
+            Ok(place.display_name.filter(|_| place.error.is_none()))
+        })
+    }
+}
+
+struct Mock {
+    places: Vec<Place>,
+}
+
+impl Provider for Mock {
+    fn search<'a>(&'a self, address: &'a str) -> Lookup<'a, Place> {
+        let key = normalize(address);
+        let found = self
+            .places
+            .iter()
+            .find(|p| normalize(&p.address) == key)
+            .or_else(|| self.places.iter().find(|p| normalize(&p.address).contains(&key)))
+            .cloned();
+        Box::pin(async move { Ok(found) })
+    }
+
+    fn reverse<'a>(&'a self, latitude: f64, longitude: f64) -> Lookup<'a, String> {
+        let found = self
+            .places
+            .iter()
+            .map(|p| (distance_meters(latitude, longitude, p.latitude, p.longitude), p))
+            .filter(|(distance, _)| *distance <= MOCK_MATCH_METERS)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, p)| p.address.clone());
+        Box::pin(async move { Ok(found) })
+    }
+}
+
+/// Great-circle distance in meters.
+pub fn distance_meters(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    const EARTH_RADIUS_METERS: f64 = 6_371_000.0;
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_phi = (lat2 - lat1).to_radians();
+    let d_lambda = (lon2 - lon1).to_radians();
+    let a = (d_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (d_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_METERS * a.sqrt().min(1.0).asin()
+}
+
+pub fn valid_coordinates(latitude: f64, longitude: f64) -> bool {
+    (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude)
+}
+
+fn normalize(address: &str) -> String {
+    address.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+fn reverse_key(latitude: f64, longitude: f64) -> String {
+    format!("{:.5},{:.5}", latitude, longitude)
+}
+
+fn load_settings() -> Result<GeocodingSettings, String> {
+    match storage::read_to_string(SETTINGS_FILE)
+        .map_err(|e| format!("Failed to read geocoding settings: {}", e))?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| format!("Failed to deserialize geocoding settings: {}", e)),
+        None => Ok(GeocodingSettings::default()),
+    }
+}
+
+fn load_cache() -> Result<Cache, String> {
+    match storage::read_to_string(CACHE_FILE)
+        .map_err(|e| format!("Failed to read geocoding cache: {}", e))?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| format!("Failed to deserialize geocoding cache: {}", e)),
+        None => Ok(Cache::default()),
+    }
+}
+
+fn save_cache(cache: &Cache) -> Result<(), String> {
+    let json = serde_json::to_string(cache)
+        .map_err(|e| format!("Failed to serialize geocoding cache: {}", e))?;
+    storage::write(CACHE_FILE, json.as_bytes())
+        .map_err(|e| format!("Failed to write geocoding cache: {}", e))
+}
+
+fn provider(settings: &GeocodingSettings) -> Result<Box<dyn Provider>, String> {
+    Ok(match settings.provider {
+        ProviderKind::Nominatim => Box::new(Nominatim {
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .map_err(|e| format!("Failed to create geocoding client: {}", e))?,
+            base_url: settings.base_url.clone(),
+            email: settings.email.clone(),
+        }),
+        ProviderKind::Mock => Box::new(Mock {
+            places: settings.mock_places.clone(),
+        }),
+    })
+}
+
+// Fails a lookup that is still waiting when the deadline passes. Cached
+// answers are ready on the first poll, so they still come through.
+async fn before_deadline<T>(
+    deadline: Instant,
+    lookup: impl Future<Output = Result<T, String>>,
+) -> Result<T, String> {
+    tokio::time::timeout(deadline.saturating_duration_since(Instant::now()), lookup)
+        .await
+        .map_err(|_| "Geocoding ran out of time for this save".to_string())?
+}
+
+/// A provider and cache shared by the lookups of one save.
+pub struct Geocoder {
+    provider: Box<dyn Provider>,
+    cache: Cache,
+    dirty: bool,
+}
+
+impl Geocoder {
+    pub fn new(provider: Box<dyn Provider>) -> Result<Self, String> {
+        Ok(Geocoder {
+            provider,
+            cache: load_cache()?,
+            dirty: false,
+        })
+    }
+
+    pub async fn search(&mut self, address: &str) -> Result<Option<Place>, String> {
+        let key = normalize(address);
+        if let Some(cached) = self.cache.forward.get(&key) {
+            return Ok(cached.clone());
+        }
+        let place = self.provider.search(address.trim()).await?;
+        self.cache.forward.insert(key, place.clone());
+        self.dirty = true;
+        Ok(place)
+    }
+
+    pub async fn reverse(&mut self, latitude: f64, longitude: f64) -> Result<Option<String>, String> {
+        let key = reverse_key(latitude, longitude);
+        if let Some(cached) = self.cache.reverse.get(&key) {
+            return Ok(cached.clone());
+        }
+        let address = self.provider.reverse(latitude, longitude).await?;
+        self.cache.reverse.insert(key, address.clone());
+        self.dirty = true;
+        Ok(address)
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        if self.dirty {
+            save_cache(&self.cache)?;
+        }
+        Ok(())
+    }
+}
+
+fn has_text(value: &Option<String>) -> bool {
+    value.as_ref().is_some_and(|v| !v.trim().is_empty())
+}
+
+/// Fills in whichever of location and coordinates is out of date on events
+/// that changed since they were last stored. Lookups that fail or run past
+/// SAVE_LOOKUP_BUDGET leave the event as it is, so saving works offline.
+pub async fn resolve_events(events: &mut [Event]) -> Result<(), String> {
+    let settings = load_settings()?;
+    if !settings.enabled {
+        return Ok(());
+    }
+    let stored: Vec<Event> = schema::load(&schema::EVENTS)?;
+    let stored: HashMap<&str, &Event> = stored.iter().map(|e| (e.id.as_str(), e)).collect();
+    let mut geocoder = Geocoder::new(provider(&settings)?)?;
+    let deadline = Instant::now() + SAVE_LOOKUP_BUDGET;
+
+    for event in events.iter_mut() {
+        let previous = stored.get(event.id.as_str());
+        let location_changed = previous.is_none_or(|p| p.location != event.location);
+        let coords_changed =
+            previous.is_none_or(|p| p.latitude != event.latitude || p.longitude != event.longitude);
+        if !location_changed && !coords_changed {
+            continue;
+        }
+
+        let has_location = has_text(&event.location);
+        let coords = match (event.latitude, event.longitude) {
+            (Some(lat), Some(lon)) if valid_coordinates(lat, lon) => Some((lat, lon)),
+            _ => None,
+        };
+
+        let resolved = match coords {
+            // Address edited without moving the pin, or no pin yet
+            _ if has_location && (coords.is_none() || !coords_changed) => {
+                let address = event.location.clone().unwrap_or_default();
+                before_deadline(deadline, geocoder.search(&address)).await.map(|place| {
+                    place.map(|p| {
+                        event.latitude = Some(p.latitude);
+                        event.longitude = Some(p.longitude);
+                    })
+                })
+            }
+            // Address cleared: the old pin no longer means anything
+            Some(_) if previous.is_some_and(|p| has_text(&p.location)) && !coords_changed => {
+                event.latitude = None;
+                event.longitude = None;
+                Ok(Some(()))
+            }
+            // Pin moved without editing the address, or no address yet
+            Some((lat, lon)) if !has_location || !location_changed => {
+                before_deadline(deadline, geocoder.reverse(lat, lon)).await.map(|address| {
+                    address.map(|a| event.location = Some(a))
+                })
+            }
+            _ => Ok(None),
+        };
+
+        match resolved {
+            Ok(Some(())) => {
+                event.updated_at =
+                    Some(chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true));
+                event.pending_sync = true;
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Failed to geocode event '{}': {}", event.title, e),
+        }
+    }
+
+    geocoder.finish()
+}
+
+/// Looks up the coordinates of an address.
+#[command]
+pub async fn geocode_address(address: String) -> Result<Option<Place>, String> {
+    let mut geocoder = Geocoder::new(provider(&load_settings()?)?)?;
+    let place = geocoder.search(&address).await;
+    geocoder.finish()?;
+    place
+}
+
+/// Looks up the address at a pair of coordinates.
+#[command]
+pub async fn reverse_geocode(latitude: f64, longitude: f64) -> Result<Option<String>, String> {
+    if !valid_coordinates(latitude, longitude) {
+        return Err(format!("{}, {} are not valid coordinates", latitude, longitude));
+    }
+    let mut geocoder = Geocoder::new(provider(&load_settings()?)?)?;
+    let address = geocoder.reverse(latitude, longitude).await;
+    geocoder.finish()?;
+    address
+}
+
+#[command]
+pub fn get_geocoding_settings() -> Result<GeocodingSettings, String> {
+    load_settings()
+}
+
+#[command]
+pub fn save_geocoding_settings(settings: GeocodingSettings) -> Result<(), String> {
+    if settings.provider == ProviderKind::Nominatim && !settings.base_url.starts_with("http") {
+        return Err(format!("'{}' is not an HTTP URL", settings.base_url));
+    }
+    let json = serde_json::to_string(&settings)
+        .map_err(|e| format!("Failed to serialize geocoding settings: {}", e))?;
+    storage::write(SETTINGS_FILE, json.as_bytes())
+        .map_err(|e| format!("Failed to write geocoding settings: {}", e))?;
+    // Cached answers may have come from a different provider
+    clear_geocoding_cache()
+}
+
+#[command]
+pub fn clear_geocoding_cache() -> Result<(), String> {
+    save_cache(&Cache::default())
+}
@@ -0,0 +1,611 @@
+// Task import
+//
+// Reads tasks exported from other tools: CSV with a column mapping, todo.txt,
+// Todoist (CSV template or JSON backup) and Microsoft To Do (Graph JSON).
+// preview_import parses a file and flags tasks that already exist, matched on
+// title and due date; import_tasks adds the rest through save_local_tasks.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::command;
+
+use crate::tasks::Priority;
+use crate::{archive, schema, Task};
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat {
+    Csv,
+    TodoTxt,
+    Todoist,
+    MicrosoftTodo,
+}
+
+/// CSV header names to read each field from. Unset fields are looked up
+/// under common header names.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct ColumnMapping {
+    title: Option<String>,
+    description: Option<String>,
+    project: Option<String>,
+    date: Option<String>,
+    completed: Option<String>,
+    completed_on: Option<String>,
+    priority: Option<String>,
+    // Separated by commas or semicolons
+    tags: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PreviewTask {
+    task: Task,
+    // Id of the existing task this one duplicates
+    duplicate_of: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ImportPreview {
+    tasks: Vec<PreviewTask>,
+    duplicates: usize,
+    // Lines or records that could not be read
+    warnings: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct ImportResult {
+    imported: usize,
+    skipped: usize,
+    warnings: Vec<String>,
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+fn new_task(title: &str) -> Task {
+    let timestamp = now();
+    Task {
+        id: uuid::Uuid::new_v4().to_string(),
+        title: title.trim().to_string(),
+        created_at: Some(timestamp.clone()),
+        updated_at: Some(timestamp),
+        pending_sync: true,
+        ..Default::default()
+    }
+}
+
+// Dates as stored on tasks: YYYY-MM-DD
+fn parse_date(value: &str) -> Option<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    let date = DateTime::parse_from_rfc3339(value)
+        .map(|t| t.date_naive())
+        .ok()
+        .or_else(|| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f").ok().map(|t| t.date()))
+        .or_else(|| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").ok().map(|t| t.date()))
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok())
+        .or_else(|| NaiveDate::parse_from_str(value, "%m/%d/%Y").ok())
+        .or_else(|| NaiveDate::parse_from_str(value, "%d %b %Y").ok())?;
+    Some(date.format("%Y-%m-%d").to_string())
+}
+
+// Completion timestamps as stored on tasks: RFC 3339 in UTC
+fn parse_timestamp(value: &str) -> Option<String> {
+    let value = value.trim();
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&chrono::Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+                .ok()
+                .map(|t| t.and_utc())
+        })
+        .or_else(|| parse_date(value).and_then(|d| format!("{}T00:00:00Z", d).parse().ok()))
+        .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+}
+
+fn parse_priority(value: &str) -> Priority {
+    match value.trim().to_lowercase().as_str() {
+        "urgent" | "p1" | "a" => Priority::Urgent,
+        "high" | "p2" | "b" => Priority::High,
+        "medium" | "normal" | "p3" | "c" => Priority::Medium,
+        "low" | "d" => Priority::Low,
+        _ => Priority::None,
+    }
+}
+
+fn parse_bool(value: &str) -> bool {
+    matches!(value.trim().to_lowercase().as_str(), "true" | "yes" | "y" | "1" | "x" | "done" | "completed")
+}
+
+fn split_tags(value: &str) -> Vec<String> {
+    value
+        .split([',', ';'])
+        .map(|t| t.trim().trim_start_matches(['@', '#']).to_string())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+fn mark_completed(task: &mut Task, completed_on: Option<String>) {
+    task.completed = true;
+    task.completed_on = completed_on.or_else(|| Some(now()));
+}
+
+fn parse_csv(content: &str, mapping: &ColumnMapping, warnings: &mut Vec<String>) -> Result<Vec<Task>, String> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(content.as_bytes());
+    let headers = read_headers(&mut reader)?;
+    let rows = readable_rows(&mut reader, warnings);
+    parse_rows(&headers, rows, mapping, warnings)
+}
+
+fn read_headers(reader: &mut csv::Reader<&[u8]>) -> Result<Vec<String>, String> {
+    Ok(reader
+        .headers()
+        .map_err(|e| format!("Failed to read CSV header: {}", e))?
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect())
+}
+
+// Records paired with the row number the user sees in their file; rows that
+// can't be read are reported and skipped
+fn readable_rows(reader: &mut csv::Reader<&[u8]>, warnings: &mut Vec<String>) -> Vec<(usize, csv::StringRecord)> {
+    reader
+        .records()
+        .enumerate()
+        .filter_map(|(row, record)| match record {
+            Ok(record) => Some((row + 2, record)),
+            Err(e) => {
+                warnings.push(format!("Row {}: {}", row + 2, e));
+                None
+            }
+        })
+        .collect()
+}
+
+fn parse_rows(
+    headers: &[String],
+    rows: Vec<(usize, csv::StringRecord)>,
+    mapping: &ColumnMapping,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<Task>, String> {
+    let column = |mapped: &Option<String>, defaults: &[&str]| -> Option<usize> {
+        match mapped {
+            Some(name) => headers.iter().position(|h| *h == name.trim().to_lowercase()),
+            None => defaults.iter().find_map(|d| headers.iter().position(|h| h == d)),
+        }
+    };
+    let title = column(&mapping.title, &["title", "name", "task", "content", "subject"])
+        .ok_or_else(|| "CSV has no title column".to_string())?;
+    let description = column(&mapping.description, &["description", "notes", "note", "body"]);
+    let project = column(&mapping.project, &["project", "list", "folder", "section"]);
+    let date = column(&mapping.date, &["date", "due", "due date", "due_date", "deadline"]);
+    let completed = column(&mapping.completed, &["completed", "done", "status"]);
+    let completed_on = column(&mapping.completed_on, &["completed_on", "completed at", "completed date", "completion date"]);
+    let priority = column(&mapping.priority, &["priority", "importance"]);
+    let tags = column(&mapping.tags, &["tags", "labels", "categories"]);
+
+    let mut tasks = Vec::new();
+    for (row, record) in rows {
+        let field = |index: Option<usize>| index.and_then(|i| record.get(i)).unwrap_or("").trim();
+
+        if field(Some(title)).is_empty() {
+            warnings.push(format!("Row {}: no title", row));
+            continue;
+        }
+        let mut task = new_task(field(Some(title)));
+        task.description = field(description).to_string();
+        task.project = field(project).to_string();
+        task.priority = parse_priority(field(priority));
+        task.tags = split_tags(field(tags));
+        if let Some(d) = parse_date(field(date)) {
+            task.date = d;
+        } else if !field(date).is_empty() {
+            warnings.push(format!("Row {}: unrecognized date '{}'", row, field(date)));
+        }
+        let done_on = parse_timestamp(field(completed_on));
+        if parse_bool(field(completed)) || done_on.is_some() {
+            mark_completed(&mut task, done_on);
+        }
+        tasks.push(task);
+    }
+    Ok(tasks)
+}
+
+fn todo_txt_priority(letter: &str) -> Priority {
+    match parse_priority(letter) {
+        // Letters beyond D are still a priority
+        Priority::None if letter.len() == 1 && letter.chars().all(|c| c.is_ascii_uppercase()) => Priority::Low,
+        priority => priority,
+    }
+}
+
+fn take_priority(words: &mut Vec<&str>) -> Priority {
+    match words.first() {
+        Some(w) if w.len() == 3 && w.starts_with('(') && w.ends_with(')') => {
+            let priority = todo_txt_priority(&w[1..2]);
+            words.remove(0);
+            priority
+        }
+        _ => Priority::None,
+    }
+}
+
+// x 2024-05-02 2024-05-01 (A) Call mom +Family @phone due:2024-05-03
+fn parse_todo_txt(content: &str, warnings: &mut Vec<String>) -> Vec<Task> {
+    let mut tasks = Vec::new();
+    for (line_number, line) in content.lines().enumerate() {
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+
+        let completed = words[0] == "x";
+        if completed {
+            words.remove(0);
+        }
+        // Completed tasks may carry the priority before or after their dates
+        let mut priority = take_priority(&mut words);
+        let mut dates = Vec::new();
+        while dates.len() < 2 && words.first().is_some_and(|w| NaiveDate::parse_from_str(w, "%Y-%m-%d").is_ok()) {
+            dates.push(words.remove(0));
+        }
+        if priority == Priority::None {
+            priority = take_priority(&mut words);
+        }
+
+        let mut title = Vec::new();
+        let mut projects = Vec::new();
+        let mut tags = Vec::new();
+        let mut due = None;
+        for word in words {
+            if let Some(project) = word.strip_prefix('+').filter(|p| !p.is_empty()) {
+                projects.push(project.replace('_', " "));
+            } else if let Some(context) = word.strip_prefix('@').filter(|c| !c.is_empty()) {
+                tags.push(context.to_string());
+            } else if let Some(date) = word.strip_prefix("due:") {
+                due = parse_date(date);
+            } else if let Some(letter) = word.strip_prefix("pri:") {
+                priority = todo_txt_priority(letter);
+            } else {
+                title.push(word);
+            }
+        }
+
+        if title.is_empty() {
+            warnings.push(format!("Line {}: no title", line_number + 1));
+            continue;
+        }
+        let mut task = new_task(&title.join(" "));
+        task.priority = priority;
+        task.tags = tags;
+        task.date = due.unwrap_or_default();
+        task.project = projects.first().cloned().unwrap_or_default();
+        // Completed tasks list the completion date first, then the creation date
+        let (done_on, created) = if completed {
+            (dates.first(), dates.get(1))
+        } else {
+            (None, dates.first())
+        };
+        if let Some(created) = created.and_then(|d| parse_timestamp(d)) {
+            task.created_at = Some(created);
+        }
+        if completed {
+            mark_completed(&mut task, done_on.and_then(|d| parse_timestamp(d)));
+        }
+        tasks.push(task);
+    }
+    tasks
+}
+
+fn str_field<'a>(value: &'a Value, key: &str) -> &'a str {
+    value.get(key).and_then(Value::as_str).unwrap_or("")
+}
+
+fn string_list(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .map(|items| items.iter().filter_map(Value::as_str).map(String::from).collect())
+        .unwrap_or_default()
+}
+
+// Todoist backup or API JSON: {"projects": [...], "items": [...]} or a bare list of items
+fn parse_todoist_json(value: &Value, warnings: &mut Vec<String>) -> Vec<Task> {
+    let projects: HashMap<String, String> = value
+        .get("projects")
+        .and_then(Value::as_array)
+        .map(|projects| {
+            projects
+                .iter()
+                .map(|p| (json_id(&p["id"]), str_field(p, "name").to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    let items = value
+        .get("items")
+        .or_else(|| value.get("tasks"))
+        .unwrap_or(value)
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+
+    let mut tasks = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        let title = str_field(item, "content");
+        if title.trim().is_empty() {
+            warnings.push(format!("Item {}: no content", index + 1));
+            continue;
+        }
+        let mut task = new_task(title);
+        task.description = str_field(item, "description").to_string();
+        task.project = projects.get(&json_id(&item["project_id"])).cloned().unwrap_or_default();
+        task.tags = string_list(&item["labels"]);
+        task.date = parse_date(str_field(&item["due"], "date")).unwrap_or_default();
+        // The API counts priorities upwards: 4 is the app's p1
+        task.priority = match item["priority"].as_u64() {
+            Some(4) => Priority::Urgent,
+            Some(3) => Priority::High,
+            Some(2) => Priority::Medium,
+            _ => Priority::None,
+        };
+        if let Some(added) = parse_timestamp(str_field(item, "added_at")) {
+            task.created_at = Some(added);
+        }
+        let done_on = parse_timestamp(str_field(item, "completed_at"));
+        if item["checked"].as_bool().unwrap_or(false) || item["is_completed"].as_bool().unwrap_or(false) || done_on.is_some() {
+            mark_completed(&mut task, done_on);
+        }
+        tasks.push(task);
+    }
+    tasks
+}
+
+fn json_id(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+// Todoist template priorities count downwards: 1 is p1, 4 is no priority
+fn todoist_priority(value: &str) -> &'static str {
+    match value.trim() {
+        "1" => "p1",
+        "2" => "p2",
+        "3" => "p3",
+        _ => "",
+    }
+}
+
+// Todoist's CSV project template: TYPE, CONTENT, DESCRIPTION, PRIORITY, ..., DATE
+fn parse_todoist_csv(content: &str, project: &str, warnings: &mut Vec<String>) -> Result<Vec<Task>, String> {
+    let mapping = ColumnMapping {
+        title: Some("content".to_string()),
+        date: Some("date".to_string()),
+        priority: Some("priority".to_string()),
+        ..Default::default()
+    };
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(content.as_bytes());
+    let headers = read_headers(&mut reader)?;
+    let type_column = headers.iter().position(|h| h == "type");
+    let priority_column = headers.iter().position(|h| h == "priority");
+
+    // Sections and notes share the file with tasks; keep the task rows only,
+    // with priorities rewritten to names parse_rows reads
+    let rows = readable_rows(&mut reader, warnings)
+        .into_iter()
+        .filter(|(_, record)| type_column.and_then(|i| record.get(i)).is_none_or(|t| t == "task"))
+        .map(|(row, record)| {
+            let fields = record
+                .iter()
+                .enumerate()
+                .map(|(i, field)| if Some(i) == priority_column { todoist_priority(field) } else { field });
+            (row, fields.collect())
+        })
+        .collect();
+
+    let mut tasks = parse_rows(&headers, rows, &mapping, warnings)?;
+    for task in tasks.iter_mut() {
+        task.project = project.to_string();
+    }
+    Ok(tasks)
+}
+
+// Microsoft To Do through Graph: {"value": [tasks]} for one list, or a list of
+// {"displayName": ..., "tasks": [...]} for several
+fn parse_microsoft_todo(value: &Value, warnings: &mut Vec<String>) -> Vec<Task> {
+    let lists: Vec<(String, Vec<Value>)> = match value.get("value") {
+        Some(items) if items.as_array().is_some_and(|i| i.iter().all(|t| t.get("tasks").is_none())) => {
+            vec![(str_field(value, "displayName").to_string(), items.as_array().cloned().unwrap_or_default())]
+        }
+        _ => value
+            .get("value")
+            .or_else(|| value.get("lists"))
+            .unwrap_or(value)
+            .as_array()
+            .map(|lists| {
+                lists
+                    .iter()
+                    .map(|l| (str_field(l, "displayName").to_string(), l["tasks"].as_array().cloned().unwrap_or_default()))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    };
+
+    let mut tasks = Vec::new();
+    for (list, items) in lists {
+        for (index, item) in items.iter().enumerate() {
+            let title = str_field(item, "title");
+            if title.trim().is_empty() {
+                warnings.push(format!("{} item {}: no title", list, index + 1));
+                continue;
+            }
+            let mut task = new_task(title);
+            task.description = str_field(&item["body"], "content").to_string();
+            task.project = list.clone();
+            task.tags = string_list(&item["categories"]);
+            task.date = parse_date(str_field(&item["dueDateTime"], "dateTime")).unwrap_or_default();
+            task.priority = match str_field(item, "importance") {
+                "high" => Priority::High,
+                "low" => Priority::Low,
+                _ => Priority::None,
+            };
+            if let Some(created) = parse_timestamp(str_field(item, "createdDateTime")) {
+                task.created_at = Some(created);
+            }
+            if str_field(item, "status") == "completed" {
+                mark_completed(&mut task, parse_timestamp(str_field(&item["completedDateTime"], "dateTime")));
+            }
+            tasks.push(task);
+        }
+    }
+    tasks
+}
+
+fn parse_file(path: &str, format: ImportFormat, mapping: &ColumnMapping) -> Result<(Vec<Task>, Vec<String>), String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read import file: {}", e))?;
+    let content = content.trim_start_matches('\u{feff}');
+    let parse_json = || serde_json::from_str::<Value>(content).map_err(|e| format!("Failed to parse import file: {}", e));
+
+    let mut warnings = Vec::new();
+    let tasks = match format {
+        ImportFormat::Csv => parse_csv(content, mapping, &mut warnings)?,
+        ImportFormat::TodoTxt => parse_todo_txt(content, &mut warnings),
+        ImportFormat::Todoist if content.starts_with(['{', '[']) => parse_todoist_json(&parse_json()?, &mut warnings),
+        ImportFormat::Todoist => {
+            // A Todoist CSV export holds one project, named after the file
+            let project = std::path::Path::new(path)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default();
+            parse_todoist_csv(content, project, &mut warnings)?
+        }
+        ImportFormat::MicrosoftTodo => parse_microsoft_todo(&parse_json()?, &mut warnings),
+    };
+    Ok((tasks, warnings))
+}
+
+fn duplicate_key(task: &Task) -> (String, String) {
+    (task.title.trim().to_lowercase(), task.date.clone())
+}
+
+fn preview(path: &str, format: ImportFormat, mapping: &ColumnMapping) -> Result<ImportPreview, String> {
+    let (tasks, warnings) = parse_file(path, format, mapping)?;
+
+    let existing: Vec<Task> = schema::load(&schema::TASKS)?;
+    let archived = archive::load_archive()?.into_iter().map(|a| a.task);
+    let mut known: HashMap<(String, String), String> = existing
+        .into_iter()
+        .chain(archived)
+        .map(|t| (duplicate_key(&t), t.id))
+        .collect();
+
+    let mut duplicates = 0;
+    let tasks = tasks
+        .into_iter()
+        .map(|task| {
+            let key = duplicate_key(&task);
+            let duplicate_of = known.get(&key).cloned();
+            if duplicate_of.is_some() {
+                duplicates += 1;
+            } else {
+                // Repeats within the file count as duplicates of the first one
+                known.insert(key, task.id.clone());
+            }
+            PreviewTask { task, duplicate_of }
+        })
+        .collect();
+
+    Ok(ImportPreview { tasks, duplicates, warnings })
+}
+
+/// Parses a file without writing anything.
+#[command]
+pub fn preview_import(path: String, format: ImportFormat, mapping: Option<ColumnMapping>) -> Result<ImportPreview, String> {
+    preview(&path, format, &mapping.unwrap_or_default())
+}
+
+/// Imports a file. Duplicates are skipped unless `include_duplicates` is set.
+#[command]
+pub fn import_tasks(
+    path: String,
+    format: ImportFormat,
+    mapping: Option<ColumnMapping>,
+    include_duplicates: Option<bool>,
+) -> Result<ImportResult, String> {
+    let preview = preview(&path, format, &mapping.unwrap_or_default())?;
+    let include_duplicates = include_duplicates.unwrap_or(false);
+    let in_file: HashSet<String> = preview.tasks.iter().map(|p| p.task.id.clone()).collect();
+
+    let mut skipped = 0;
+    let mut new_tasks = Vec::new();
+    for PreviewTask { task, duplicate_of } in preview.tasks {
+        // Duplicates of another row in the same file are always dropped
+        let repeat = duplicate_of.as_ref().is_some_and(|id| in_file.contains(id));
+        if duplicate_of.is_some() && (!include_duplicates || repeat) {
+            skipped += 1;
+        } else {
+            new_tasks.push(task);
+        }
+    }
+
+    let imported = new_tasks.len();
+    if imported > 0 {
+        let mut tasks: Vec<Task> = schema::load(&schema::TASKS)?;
+        tasks.extend(new_tasks);
+        crate::save_local_tasks(tasks)?;
+    }
+
+    Ok(ImportResult {
+        imported,
+        skipped,
+        warnings: preview.warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_todoist_priorities_per_row() {
+        let csv = "TYPE,CONTENT,DESCRIPTION,PRIORITY,INDENT,AUTHOR,RESPONSIBLE,DATE,DATE_LANG,TIMEZONE\n\
+                   task,Call the bank,,1,1,,,2024-05-10,en,\n\
+                   section,Errands,,,,,,,,\n\
+                   task,,,2,1,,,,en,\n\
+                   task,Water plants,,4,1,,,,en,\n\
+                   task,Book flights,,2,1,,,,en,\n";
+        let mut warnings = Vec::new();
+        let tasks = parse_todoist_csv(csv, "Home", &mut warnings).unwrap();
+
+        let imported: Vec<(&str, Priority, &str)> =
+            tasks.iter().map(|t| (t.title.as_str(), t.priority, t.project.as_str())).collect();
+        assert_eq!(
+            imported,
+            vec![
+                ("Call the bank", Priority::Urgent, "Home"),
+                ("Water plants", Priority::None, "Home"),
+                ("Book flights", Priority::High, "Home"),
+            ]
+        );
+        // Numbered as in the user's file, counting the section row
+        assert_eq!(warnings, vec!["Row 4: no title".to_string()]);
+    }
+
+    #[test]
+    fn imports_past_short_todoist_rows() {
+        let csv = "TYPE,CONTENT,PRIORITY\ntask\ntask,Fine,3\n";
+        let mut warnings = Vec::new();
+        let tasks = parse_todoist_csv(csv, "", &mut warnings).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].priority, Priority::Medium);
+        assert_eq!(warnings, vec!["Row 2: no title".to_string()]);
+    }
+}
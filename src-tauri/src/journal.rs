@@ -0,0 +1,391 @@
+// Undo journal
+//
+// save_local_tasks and save_local_events replace a whole store, so every save
+// is diffed against the stored records and the difference is journaled as one
+// operation, including tasks the archive policy moved out during the save.
+// Undo writes the records as they were before the operation, redo as they
+// were after it, moving archived tasks out of and back into the archive.
+// Reverted records get a fresh updated_at so sync propagates the revert
+// instead of pulling the old state back.
+
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::command;
+
+use crate::schema::{self, Schema};
+use crate::storage;
+
+const JOURNAL_FILE: &str = "undo_journal.json";
+const MAX_OPERATIONS: usize = 100;
+// Fields that change without the user changing anything
+const IGNORED_FIELDS: &[&str] = &["pending_sync"];
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Store {
+    Tasks,
+    Events,
+}
+
+impl Store {
+    fn schema(self) -> &'static Schema {
+        match self {
+            Store::Tasks => &schema::TASKS,
+            Store::Events => &schema::EVENTS,
+        }
+    }
+
+    fn noun(self) -> &'static str {
+        match self {
+            Store::Tasks => "task",
+            Store::Events => "event",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Change {
+    id: String,
+    // None when the record did not exist on that side
+    before: Option<Value>,
+    after: Option<Value>,
+    // Position of the record before the operation, so undoing a delete puts
+    // it back where it was
+    index: Option<usize>,
+    // The task was moved into the archive rather than deleted
+    #[serde(default)]
+    archived: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Operation {
+    id: String,
+    store: Store,
+    at: String,
+    description: String,
+    changes: Vec<Change>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct Journal {
+    undo: Vec<Operation>,
+    redo: Vec<Operation>,
+}
+
+#[derive(Serialize)]
+pub struct OperationSummary {
+    id: String,
+    store: Store,
+    at: String,
+    description: String,
+    changes: usize,
+}
+
+#[derive(Serialize)]
+pub struct UndoHistory {
+    // Most recent first
+    undo: Vec<OperationSummary>,
+    redo: Vec<OperationSummary>,
+}
+
+impl Operation {
+    fn summary(&self) -> OperationSummary {
+        OperationSummary {
+            id: self.id.clone(),
+            store: self.store,
+            at: self.at.clone(),
+            description: self.description.clone(),
+            changes: self.changes.len(),
+        }
+    }
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+fn load_journal() -> Result<Journal, String> {
+    match storage::read_to_string(JOURNAL_FILE)
+        .map_err(|e| format!("Failed to read undo journal: {}", e))?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| format!("Failed to deserialize undo journal: {}", e)),
+        None => Ok(Journal::default()),
+    }
+}
+
+fn save_journal(journal: &Journal) -> Result<(), String> {
+    let json = serde_json::to_string(journal)
+        .map_err(|e| format!("Failed to serialize undo journal: {}", e))?;
+    storage::write(JOURNAL_FILE, json.as_bytes())
+        .map_err(|e| format!("Failed to write undo journal: {}", e))
+}
+
+fn record_id(record: &Value) -> Option<&str> {
+    record.get("id").and_then(Value::as_str)
+}
+
+fn same_content(a: &Value, b: &Value) -> bool {
+    let strip = |value: &Value| -> Option<Map<String, Value>> {
+        let mut map = value.as_object()?.clone();
+        for field in IGNORED_FIELDS {
+            map.remove(*field);
+        }
+        Some(map)
+    };
+    strip(a) == strip(b)
+}
+
+fn title_of(record: &Value) -> &str {
+    record.get("title").and_then(Value::as_str).unwrap_or("untitled")
+}
+
+fn describe(store: Store, changes: &[Change]) -> String {
+    let noun = store.noun();
+    if let [change] = changes {
+        let (verb, record) = match (&change.before, &change.after) {
+            (None, Some(after)) => ("Added", after),
+            (Some(before), None) if change.archived => ("Archived", before),
+            (Some(before), None) => ("Deleted", before),
+            (_, Some(after)) => ("Edited", after),
+            (None, None) => ("Changed", &Value::Null),
+        };
+        return format!("{} {} '{}'", verb, noun, title_of(record));
+    }
+    format!("Changed {} {}s", changes.len(), noun)
+}
+
+fn diff(before: &[Value], after: &[Value]) -> Vec<Change> {
+    let before_ids: HashSet<&str> = before.iter().filter_map(record_id).collect();
+    let after_by_id: HashMap<&str, &Value> = after.iter().filter_map(|r| Some((record_id(r)?, r))).collect();
+
+    let mut changes = Vec::new();
+    for (index, old) in before.iter().enumerate() {
+        let Some(id) = record_id(old) else { continue };
+        match after_by_id.get(id) {
+            Some(new) if same_content(old, new) => {}
+            new => changes.push(Change {
+                id: id.to_string(),
+                before: Some(old.clone()),
+                after: new.map(|new| (*new).clone()),
+                index: Some(index),
+                archived: false,
+            }),
+        }
+    }
+    for new in after {
+        let Some(id) = record_id(new) else { continue };
+        if !before_ids.contains(id) {
+            changes.push(Change {
+                id: id.to_string(),
+                before: None,
+                after: Some(new.clone()),
+                index: None,
+                archived: false,
+            });
+        }
+    }
+    changes
+}
+
+/// Journals the difference between the stored records and `records`, which
+/// are about to replace them.
+pub fn record<T: Serialize>(store: Store, records: &[T]) -> Result<(), String> {
+    let before: Vec<Value> = schema::load(store.schema())?;
+    let after: Vec<Value> = records
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Failed to serialize {}s for the undo journal: {}", store.noun(), e))?;
+
+    let mut changes = diff(&before, &after);
+    if changes.is_empty() {
+        return Ok(());
+    }
+    if store == Store::Tasks && changes.iter().any(|c| c.after.is_none()) {
+        // The archive policy runs before the save is journaled
+        let archived = crate::archive::archived_ids()?;
+        for change in changes.iter_mut().filter(|c| c.after.is_none()) {
+            change.archived = archived.contains(&change.id);
+        }
+    }
+
+    let mut journal = load_journal()?;
+    journal.undo.push(Operation {
+        id: uuid::Uuid::new_v4().to_string(),
+        store,
+        at: now(),
+        description: describe(store, &changes),
+        changes,
+    });
+    if journal.undo.len() > MAX_OPERATIONS {
+        let excess = journal.undo.len() - MAX_OPERATIONS;
+        journal.undo.drain(..excess);
+    }
+    journal.redo.clear();
+    save_journal(&journal)
+}
+
+/// Hashes of the attachments referenced by records in the journal, so
+/// undoing or redoing a step never points at a deleted blob.
+pub fn attachment_hashes() -> Result<HashSet<String>, String> {
+    let journal = load_journal()?;
+    Ok(journal
+        .undo
+        .iter()
+        .chain(&journal.redo)
+        .flat_map(|operation| &operation.changes)
+        .flat_map(|change| change.before.iter().chain(&change.after))
+        .filter_map(|record| record.get("attachments").and_then(Value::as_array))
+        .flatten()
+        .filter_map(|attachment| attachment.get("hash").and_then(Value::as_str))
+        .map(String::from)
+        .collect())
+}
+
+// Writes one side of an operation into its store.
+fn apply(operation: &Operation, undo: bool) -> Result<(), String> {
+    let schema = operation.store.schema();
+    let mut records: Vec<Value> = schema::load(schema)?;
+    let timestamp = now();
+
+    let mut changes: Vec<&Change> = operation.changes.iter().collect();
+    if undo {
+        // Reinsert removed records front to back so their indexes stay valid
+        changes.sort_by_key(|c| c.index);
+    }
+
+    // Undo takes archived tasks back out of the archive, redo archives them again
+    let mut unarchived = HashSet::new();
+    let mut rearchived = Vec::new();
+    for change in changes {
+        let target = if undo { &change.before } else { &change.after };
+        if change.archived {
+            if undo {
+                unarchived.insert(change.id.clone());
+            } else if let Some(before) = &change.before {
+                rearchived.push(before.clone());
+            }
+        }
+        let position = records.iter().position(|r| record_id(r) == Some(change.id.as_str()));
+        match (target, position) {
+            (Some(record), position) => {
+                let mut record = record.clone();
+                if let Some(map) = record.as_object_mut() {
+                    map.insert("updated_at".to_string(), Value::String(timestamp.clone()));
+                    map.insert("pending_sync".to_string(), Value::Bool(true));
+                }
+                match position {
+                    Some(i) => records[i] = record,
+                    None => {
+                        let index = change.index.filter(|_| undo).unwrap_or(records.len()).min(records.len());
+                        records.insert(index, record);
+                    }
+                }
+            }
+            (None, Some(i)) => {
+                records.remove(i);
+            }
+            (None, None) => {}
+        }
+    }
+
+    schema::save(schema, &records)?;
+    if !unarchived.is_empty() {
+        crate::archive::remove_from_archive(&unarchived)?;
+    }
+    if !rearchived.is_empty() {
+        crate::archive::add_to_archive(rearchived)?;
+    }
+    Ok(())
+}
+
+fn step(steps: Option<usize>, undo: bool) -> Result<Vec<OperationSummary>, String> {
+    let mut journal = load_journal()?;
+    let mut done = Vec::new();
+
+    for _ in 0..steps.unwrap_or(1).max(1) {
+        let operation = match if undo { journal.undo.pop() } else { journal.redo.pop() } {
+            Some(operation) => operation,
+            None => break,
+        };
+        apply(&operation, undo)?;
+        done.push(operation.summary());
+        if undo {
+            journal.redo.push(operation);
+        } else {
+            journal.undo.push(operation);
+        }
+        // Save after every step so the journal matches the stores even if a later step fails
+        save_journal(&journal)?;
+    }
+
+    if done.is_empty() {
+        return Err(if undo { "Nothing to undo" } else { "Nothing to redo" }.to_string());
+    }
+    Ok(done)
+}
+
+/// Reverts the last `steps` operations (default 1). Returns what was undone.
+#[command]
+pub fn undo(steps: Option<usize>) -> Result<Vec<OperationSummary>, String> {
+    step(steps, true)
+}
+
+#[command]
+pub fn redo(steps: Option<usize>) -> Result<Vec<OperationSummary>, String> {
+    step(steps, false)
+}
+
+#[command]
+pub fn get_undo_history() -> Result<UndoHistory, String> {
+    let journal = load_journal()?;
+    Ok(UndoHistory {
+        undo: journal.undo.iter().rev().map(Operation::summary).collect(),
+        redo: journal.redo.iter().rev().map(Operation::summary).collect(),
+    })
+}
+
+#[command]
+pub fn clear_undo_history() -> Result<(), String> {
+    save_journal(&Journal::default())?;
+    // Blobs only the journal still referred to are orphans now
+    crate::attachments::collect_garbage()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn diffs_records_by_id() {
+        let before = vec![
+            json!({ "id": "a", "title": "Keep", "pending_sync": false }),
+            json!({ "id": "b", "title": "Edit" }),
+            json!({ "id": "c", "title": "Delete" }),
+        ];
+        let after = vec![
+            // Only an ignored field changed
+            json!({ "id": "a", "title": "Keep", "pending_sync": true }),
+            json!({ "id": "d", "title": "Add" }),
+            json!({ "id": "b", "title": "Edited" }),
+        ];
+
+        let changes = diff(&before, &after);
+        let summary: Vec<(&str, bool, bool, Option<usize>)> = changes
+            .iter()
+            .map(|c| (c.id.as_str(), c.before.is_some(), c.after.is_some(), c.index))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("b", true, true, Some(1)), ("c", true, false, Some(2)), ("d", false, true, None)]
+        );
+        assert_eq!(changes[0].after, Some(json!({ "id": "b", "title": "Edited" })));
+        assert_eq!(describe(Store::Tasks, &changes[1..2]), "Deleted task 'Delete'");
+        assert_eq!(describe(Store::Tasks, &changes), "Changed 3 tasks");
+    }
+}
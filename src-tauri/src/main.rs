@@ -13,12 +13,38 @@ use tauri::Manager;
 use std::env;
 use fs2::FileExt;
 
+mod agenda;
+mod archive;
+mod attachments;
+mod backup;
+mod conflicts;
+mod export;
+mod focus;
+mod geocoding;
+mod import;
+mod journal;
+mod notes;
+mod profiles;
+mod projects;
+mod reminders;
+mod schema;
+mod scheduling;
+mod search;
+mod snapshots;
+mod spatial;
+mod stats;
+mod storage;
+mod tasks;
+mod time_entries;
+mod timezones;
+
 const REDIRECT_URI: &str = "oob";
 
 #[derive(serde::Serialize)]
 struct Folder {
     name: String,
     last_modified: u64,
+    project_id: Option<String>,
 }
 
 #[derive(serde::Serialize)]
@@ -64,11 +90,18 @@ struct Task {
     date: String,
     description: String,
     project: String,
+    project_id: Option<String>,
     completed: bool,
     completed_on: Option<String>,
+    created_at: Option<String>,
     updated_at: Option<String>,
     #[serde(default)]
-    pending_sync: bool
+    pending_sync: bool,
+    priority: tasks::Priority,
+    tags: Vec<String>,
+    estimated_minutes: Option<u32>,
+    parent_id: Option<String>,
+    attachments: Vec<attachments::Attachment>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -94,6 +127,10 @@ struct Event {
     updated_at: Option<String>,
     #[serde(default)]
     pending_sync: bool,
+    #[serde(default)]
+    attachments: Vec<attachments::Attachment>,
+    // IANA zone the date and times are in
+    timezone: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -201,7 +238,12 @@ async fn fetch_google_calendar_events(access_token: String) -> Result<Vec<Calend
                     location: meeting_link,
                 }
             })
-            .collect();
+            .collect::<Vec<CalendarEvent>>();
+
+        // Keep a copy for backend features (search, agenda) that need Google events
+        if let Err(e) = cache_google_events(&items) {
+            eprintln!("Failed to cache Google events: {}", e);
+        }
 
         Ok(items)
     } else {
@@ -211,6 +253,21 @@ async fn fetch_google_calendar_events(access_token: String) -> Result<Vec<Calend
     }
 }
 
+fn cache_google_events(events: &[CalendarEvent]) -> Result<(), String> {
+    let json = serde_json::to_string(events)
+        .map_err(|e| format!("Failed to serialize Google events: {}", e))?;
+    storage::write("google_events_cache.json", json.as_bytes())
+}
+
+#[command]
+fn load_google_events_cache() -> Result<Vec<CalendarEvent>, String> {
+    match storage::read_to_string("google_events_cache.json")? {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| format!("Failed to deserialize Google events: {}", e)),
+        None => Ok(Vec::new()),
+    }
+}
+
 #[command]
 async fn refresh_google_tokens(refresh_token: String) -> Result<TokenResponse, String> {
     let client = Client::new();
@@ -266,6 +323,8 @@ fn get_project_folders(path: String) -> Result<Vec<Folder>, String> {
         return Err("Path is not a directory".into());
     }
 
+    let projects = projects::load_projects().unwrap_or_default();
+
     let mut folders = Vec::new();
     for entry in fs::read_dir(path).map_err(|e| e.to_string())? {
         let entry = entry.map_err(|e| e.to_string())?;
@@ -283,6 +342,7 @@ fn get_project_folders(path: String) -> Result<Vec<Folder>, String> {
                 folders.push(Folder {
                     name: name.to_string(),
                     last_modified,
+                    project_id: projects::project_for_folder(&projects, &path),
                 });
             }
         }
@@ -421,33 +481,18 @@ fn visit_dirs(dir: &PathBuf, music_files: &mut Vec<MusicFile>) -> std::io::Resul
 // Github
 #[command]
 fn cache_github_repos(data: String) -> Result<(), String> {
-    let cache_path = dirs::data_local_dir()
-        .ok_or_else(|| "Failed to get local data directory".to_string())?
-        .join("github_repos_cache.json");
-    
-    fs::write(&cache_path, data).map_err(|e| e.to_string())?;
-    Ok(())
+    storage::write("github_repos_cache.json", data.as_bytes())
 }
 
 #[command]
 fn read_github_repos_cache() -> Result<String, String> {
-    let cache_path = dirs::data_local_dir()
-        .ok_or_else(|| "Failed to get local data directory".to_string())?
-        .join("github_repos_cache.json");
-
-    if cache_path.exists() {
-        let data = fs::read_to_string(&cache_path).map_err(|e| e.to_string())?;
-        Ok(data)
-    } else {
-        Err("Cache file does not exist".to_string())
-    }
+    storage::read_to_string("github_repos_cache.json")?
+        .ok_or_else(|| "Cache file does not exist".to_string())
 }
 
 #[command]
 fn clear_github_cache() -> Result<(), String> {
-    let cache_path = dirs::data_local_dir()
-        .ok_or_else(|| "Failed to get local data directory".to_string())?
-        .join("github_repos_cache.json");
+    let cache_path = profiles::data_file("github_repos_cache.json")?;
 
     if cache_path.exists() {
         fs::remove_file(&cache_path).map_err(|e| e.to_string())?;
@@ -460,128 +505,91 @@ fn clear_github_cache() -> Result<(), String> {
 // Asana Tasks
 #[command]
 fn cache_asana_tasks(data: String) -> Result<(), String> {
-    let cache_path = dirs::data_local_dir()
-        .ok_or_else(|| "Failed to get local data directory".to_string())?
-        .join("asana_tasks_cache.json");
-    
-    fs::write(&cache_path, data).map_err(|e| e.to_string())?;
-    Ok(())
+    storage::write("asana_tasks_cache.json", data.as_bytes())
 }
 
 #[command]
 fn read_asana_tasks_cache() -> Result<String, String> {
-    let cache_path = dirs::data_local_dir()
-        .ok_or_else(|| "Failed to get local data directory".to_string())?
-        .join("asana_tasks_cache.json");
-
-    if cache_path.exists() {
-        let data = fs::read_to_string(&cache_path).map_err(|e| e.to_string())?;
-        Ok(data)
-    } else {
-        Err("Asana tasks cache file does not exist".to_string())
-    }
+    storage::read_to_string("asana_tasks_cache.json")?
+        .ok_or_else(|| "Asana tasks cache file does not exist".to_string())
 }
 
 // Asana Users
 #[command]
 fn cache_asana_user_details(data: String) -> Result<(), String> {
-  let cache_path = dirs::data_local_dir()
-    .ok_or_else(|| "Failed to get local data directory".to_string())?
-    .join("asana_user_details_cache.json");
-
-  fs::write(&cache_path, data).map_err(|e| e.to_string())?;
-  Ok(())
+  storage::write("asana_user_details_cache.json", data.as_bytes())
 }
 
 #[command]
 fn read_asana_user_details_cache() -> Result<String, String> {
-  let cache_path = dirs::data_local_dir()
-    .ok_or_else(|| "Failed to get local data directory".to_string())?
-    .join("asana_user_details_cache.json");
+  storage::read_to_string("asana_user_details_cache.json")?
+    .ok_or_else(|| "Asana user details cache file does not exist".to_string())
+}
 
-  if cache_path.exists() {
-    let data = fs::read_to_string(&cache_path).map_err(|e| e.to_string())?;
-    Ok(data)
-  } else {
-    Err("Asana user details cache file does not exist".to_string())
-  }
+// Google Tokens
+#[command]
+fn cache_google_tokens(data: String) -> Result<(), String> {
+    storage::write("google_tokens_cache.json", data.as_bytes())
+}
+
+#[command]
+fn read_google_tokens_cache() -> Result<String, String> {
+    storage::read_to_string("google_tokens_cache.json")?
+        .ok_or_else(|| "Google tokens cache file does not exist".to_string())
 }
 
 //  Local Tasks
 #[command]
-fn save_local_tasks(tasks: Vec<Task>) -> Result<(), String> {
-    let tasks_json = serde_json::to_string(&tasks)
-        .map_err(|e| format!("Failed to serialize tasks: {}", e))?;
-    
-    let cache_path = dirs::data_local_dir()
-        .ok_or_else(|| "Failed to get local data directory".to_string())?
-        .join("local_tasks_cache.json");
-    
-    fs::write(&cache_path, tasks_json)
-        .map_err(|e| format!("Failed to write tasks to file: {}", e))?;
-    
-    Ok(())
+fn save_local_tasks(mut tasks: Vec<Task>) -> Result<Vec<Task>, String> {
+    projects::link_tasks(&mut tasks)?;
+    tasks::roll_up_completion(&mut tasks);
+    archive::apply_policy(&mut tasks)?;
+    tasks::store(&tasks)?;
+    Ok(tasks)
 }
 
 #[command]
 fn load_local_tasks() -> Result<Vec<Task>, String> {
-    let cache_path = dirs::data_local_dir()
-        .ok_or_else(|| "Failed to get local data directory".to_string())?
-        .join("local_tasks_cache.json");
-
-    if !cache_path.exists() {
-        return Ok(Vec::new());
+    let mut tasks: Vec<Task> = schema::load(&schema::TASKS)?;
+    if archive::apply_policy(&mut tasks)? > 0 {
+        tasks::store(&tasks)?;
     }
-
-    let tasks_json = fs::read_to_string(&cache_path)
-        .map_err(|e| format!("Failed to read tasks file: {}", e))?;
-    
-    let tasks: Vec<Task> = serde_json::from_str(&tasks_json)
-        .map_err(|e| format!("Failed to deserialize tasks: {}", e))?;
-    
+    projects::link_loaded_tasks(&mut tasks)?;
     Ok(tasks)
 }
 
 // Local events
-#[command]
-fn save_local_events(events: Vec<Event>) -> Result<(), String> {
-    let cache_path = dirs::data_local_dir()
-        .ok_or_else(|| "Failed to get local data directory".to_string())?
-        .join("local_events_cache.json");
 
-    let events_json = serde_json::to_string(&events)
-        .map_err(|e| format!("Failed to serialize events: {}", e))?;
+// Geocoding can hold a save for seconds; saves queue up behind each other so
+// an older one never lands after a newer one
+static EVENT_SAVES: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
 
-    fs::write(&cache_path, events_json)
-        .map_err(|e| format!("Failed to write events file: {}", e))?;
+#[command]
+async fn save_local_events(mut events: Vec<Event>) -> Result<Vec<Event>, String> {
+    let _saving = EVENT_SAVES.lock().await;
+    // Left unset when the system zone is unknown, so the event follows the user's zone
+    let zone = timezones::system_zone().map(|zone| zone.name().to_string());
+    for event in events.iter_mut().filter(|e| e.timezone.is_none()) {
+        event.timezone = zone.clone();
+    }
+    geocoding::resolve_events(&mut events).await?;
+    store_local_events(&events)?;
+    Ok(events)
+}
 
-    Ok(())
+fn store_local_events(events: &[Event]) -> Result<(), String> {
+    journal::record(journal::Store::Events, events)?;
+    schema::save(&schema::EVENTS, events)
 }
 
 #[command]
 fn load_local_events() -> Result<Vec<Event>, String> {
-    let cache_path = dirs::data_local_dir()
-        .ok_or_else(|| "Failed to get local data directory".to_string())?
-        .join("local_events_cache.json");
-
-    if !cache_path.exists() {
-        return Ok(Vec::new());
-    }
-
-    let events_json = fs::read_to_string(&cache_path)
-        .map_err(|e| format!("Failed to read events file: {}", e))?;
-
-    let events: Vec<Event> = serde_json::from_str(&events_json)
-        .map_err(|e| format!("Failed to deserialize events: {}", e))?;
-
-    Ok(events)
+    schema::load(&schema::EVENTS)
 }
 
 #[command]
 fn clear_local_events() -> Result<(), String> {
-    let cache_path = dirs::data_local_dir()
-        .ok_or_else(|| "Failed to get local data directory".to_string())?
-        .join("local_events_cache.json");
+    let cache_path = profiles::data_file("local_events_cache.json")?;
 
     if cache_path.exists() {
         fs::remove_file(&cache_path).map_err(|e| format!("Failed to remove events file: {}", e))?;
@@ -626,9 +634,18 @@ fn main() {
 fn run_app() {
     dotenv::dotenv().ok();
 
+    if let Err(e) = profiles::init_from_args() {
+        eprintln!("Failed to load profile: {}", e);
+        std::process::exit(1);
+    }
+
     tauri::Builder::default()
         .setup(|app| {
             let app_handle = app.handle();
+
+            snapshots::start_scheduler();
+            focus::start_ticker(app_handle.clone());
+            reminders::start_scheduler(app_handle.clone());
             
             // First, check if the window exists
             if let Some(existing_window) = app_handle.get_window("main") {
@@ -700,7 +717,97 @@ fn run_app() {
             save_local_events,
             load_local_events,
             clear_local_events,
-            refresh_google_tokens
+            refresh_google_tokens,
+            load_google_events_cache,
+            cache_google_tokens,
+            read_google_tokens_cache,
+            profiles::list_profiles,
+            profiles::get_active_profile,
+            profiles::create_profile,
+            profiles::switch_profile,
+            profiles::delete_profile,
+            backup::export_backup,
+            backup::preview_backup,
+            backup::import_backup,
+            snapshots::get_snapshot_settings,
+            snapshots::save_snapshot_settings,
+            snapshots::list_snapshots,
+            snapshots::create_snapshot,
+            snapshots::restore_snapshot,
+            storage::get_encryption_status,
+            storage::enable_encryption,
+            storage::disable_encryption,
+            storage::unlock_encryption,
+            tasks::query_tasks,
+            search::search_all,
+            projects::list_projects,
+            projects::create_project,
+            projects::update_project,
+            projects::rename_project,
+            projects::delete_project,
+            stats::get_task_stats,
+            focus::get_focus_settings,
+            focus::save_focus_settings,
+            focus::get_focus_state,
+            focus::start_focus,
+            focus::pause_focus,
+            focus::stop_focus,
+            focus::skip_focus,
+            focus::list_focus_sessions,
+            focus::get_focus_time,
+            time_entries::list_time_entries,
+            time_entries::save_time_entries,
+            time_entries::start_time_entry,
+            time_entries::stop_time_entry,
+            time_entries::add_time_entry,
+            time_entries::update_time_entry,
+            time_entries::delete_time_entry,
+            time_entries::get_time_report,
+            time_entries::export_time_report_csv,
+            archive::get_archive_settings,
+            archive::save_archive_settings,
+            archive::list_archived_tasks,
+            archive::list_archived_task_ids,
+            archive::archive_completed_tasks,
+            archive::restore_archived_tasks,
+            archive::purge_archived_tasks,
+            archive::clear_purged_task_ids,
+            journal::undo,
+            journal::redo,
+            journal::get_undo_history,
+            journal::clear_undo_history,
+            import::preview_import,
+            import::import_tasks,
+            export::export_report,
+            notes::list_notes,
+            notes::read_note,
+            notes::read_note_revision,
+            notes::write_note,
+            notes::delete_note,
+            attachments::attach_file,
+            attachments::list_attachments,
+            attachments::remove_attachment,
+            attachments::export_attachment,
+            attachments::collect_attachment_garbage,
+            geocoding::geocode_address,
+            geocoding::reverse_geocode,
+            geocoding::get_geocoding_settings,
+            geocoding::save_geocoding_settings,
+            geocoding::clear_geocoding_cache,
+            spatial::query_events_near,
+            spatial::query_events_in_bbox,
+            reminders::get_travel_plan,
+            reminders::get_travel_settings,
+            reminders::save_travel_settings,
+            conflicts::find_event_conflicts,
+            conflicts::check_event_conflicts,
+            scheduling::find_free_slots,
+            timezones::get_user_timezone,
+            timezones::list_timezones,
+            timezones::get_event_times,
+            timezones::convert_time,
+            agenda::get_agenda,
+            agenda::get_daily_briefing
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,228 @@
+// Notes
+//
+// Markdown notes linked to a task, event or project. notes.json indexes them;
+// each note keeps its files under notes/<id>/:
+//
+//   note.md              current content
+//   revisions/<rev>.md   earlier contents, oldest pruned first
+//
+// Attached files live in the shared attachment store, like those of tasks and
+// events. All of them go through storage, so they are encrypted along with
+// the rest of the profile and included in backups.
+
+use std::fs;
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+use crate::schema::{self, Schema};
+use crate::attachments::{self, Attachment};
+use crate::{profiles, storage};
+
+pub const NOTES: Schema = Schema {
+    file: "notes.json",
+    version: 1,
+    migrations: &[schema::wrap_items],
+};
+
+const MAX_REVISIONS: usize = 50;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetKind {
+    #[default]
+    Task,
+    Event,
+    Project,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Revision {
+    id: String,
+    saved_at: String,
+    size: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Note {
+    pub id: String,
+    pub title: String,
+    pub target_kind: TargetKind,
+    pub target_id: String,
+    pub created_at: String,
+    pub updated_at: String,
+    // Oldest first
+    pub revisions: Vec<Revision>,
+    pub attachments: Vec<Attachment>,
+}
+
+#[derive(Serialize)]
+pub struct NoteContent {
+    note: Note,
+    content: String,
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+fn content_file(id: &str) -> String {
+    format!("notes/{}/note.md", id)
+}
+
+fn revision_file(id: &str, revision: &str) -> String {
+    format!("notes/{}/revisions/{}.md", id, revision)
+}
+
+// Timestamped so revision files sort by age, with a counter suffix for saves
+// within the same millisecond.
+fn revision_id(note: &Note) -> String {
+    let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%S%3f").to_string();
+    let taken = |candidate: &str| note.revisions.iter().any(|r| r.id == candidate);
+    if !taken(&stamp) {
+        return stamp;
+    }
+    (2..)
+        .map(|n| format!("{}-{}", stamp, n))
+        .find(|candidate| !taken(candidate))
+        .unwrap_or(stamp)
+}
+
+pub fn load_notes() -> Result<Vec<Note>, String> {
+    schema::load(&NOTES)
+}
+
+fn find<'a>(notes: &'a mut [Note], id: &str) -> Result<&'a mut Note, String> {
+    notes
+        .iter_mut()
+        .find(|n| n.id == id)
+        .ok_or_else(|| format!("Note '{}' does not exist", id))
+}
+
+fn read_text(name: &str) -> Result<String, String> {
+    storage::read_to_string(name)
+        .map_err(|e| format!("Failed to read note: {}", e))
+        .map(Option::unwrap_or_default)
+}
+
+/// Notes, most recently updated first, optionally only those of one target.
+#[command]
+pub fn list_notes(target_kind: Option<TargetKind>, target_id: Option<String>) -> Result<Vec<Note>, String> {
+    let mut notes: Vec<Note> = load_notes()?
+        .into_iter()
+        .filter(|n| target_kind.is_none_or(|kind| n.target_kind == kind))
+        .filter(|n| target_id.as_ref().is_none_or(|id| n.target_id == *id))
+        .collect();
+    notes.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    Ok(notes)
+}
+
+#[command]
+pub fn read_note(id: String) -> Result<NoteContent, String> {
+    let mut notes = load_notes()?;
+    let note = find(&mut notes, &id)?.clone();
+    let content = read_text(&content_file(&id))?;
+    Ok(NoteContent { note, content })
+}
+
+#[command]
+pub fn read_note_revision(id: String, revision: String) -> Result<String, String> {
+    let mut notes = load_notes()?;
+    let note = find(&mut notes, &id)?;
+    if !note.revisions.iter().any(|r| r.id == revision) {
+        return Err(format!("Note '{}' has no revision '{}'", id, revision));
+    }
+    read_text(&revision_file(&id, &revision))
+}
+
+/// Creates a note when `id` is None, otherwise saves new content for it. The
+/// previous content is kept as a revision.
+#[command]
+pub fn write_note(
+    id: Option<String>,
+    target_kind: TargetKind,
+    target_id: String,
+    title: String,
+    content: String,
+) -> Result<Note, String> {
+    if target_id.trim().is_empty() {
+        return Err("A note must be linked to a task, event or project".to_string());
+    }
+
+    let mut notes = load_notes()?;
+    let timestamp = now();
+    let index = match id {
+        Some(id) => {
+            let note = find(&mut notes, &id)?;
+            let previous = read_text(&content_file(&id))?;
+            if previous != content {
+                let revision = revision_id(note);
+                storage::write(&revision_file(&id, &revision), previous.as_bytes())
+                    .map_err(|e| format!("Failed to save note revision: {}", e))?;
+                note.revisions.push(Revision {
+                    id: revision,
+                    saved_at: note.updated_at.clone(),
+                    size: previous.len(),
+                });
+                while note.revisions.len() > MAX_REVISIONS {
+                    let oldest = note.revisions.remove(0);
+                    let _ = fs::remove_file(profiles::data_file(&revision_file(&id, &oldest.id))?);
+                }
+            }
+            notes.iter().position(|n| n.id == id).unwrap_or_default()
+        }
+        None => {
+            notes.push(Note {
+                id: uuid::Uuid::new_v4().to_string(),
+                created_at: timestamp.clone(),
+                ..Default::default()
+            });
+            notes.len() - 1
+        }
+    };
+
+    let note = &mut notes[index];
+    note.title = title.trim().to_string();
+    note.target_kind = target_kind;
+    note.target_id = target_id;
+    note.updated_at = timestamp;
+    storage::write(&content_file(&note.id), content.as_bytes())
+        .map_err(|e| format!("Failed to write note: {}", e))?;
+
+    let note = note.clone();
+    schema::save(&NOTES, &notes)?;
+    Ok(note)
+}
+
+/// Deletes a note with its revisions, and attachments nothing else uses.
+#[command]
+pub fn delete_note(id: String) -> Result<(), String> {
+    let mut notes = load_notes()?;
+    let before = notes.len();
+    notes.retain(|n| n.id != id);
+    if notes.len() == before {
+        return Err(format!("Note '{}' does not exist", id));
+    }
+
+    let dir = profiles::data_file(&format!("notes/{}", id))?;
+    if dir.exists() {
+        fs::remove_dir_all(&dir).map_err(|e| format!("Failed to delete note files: {}", e))?;
+    }
+    schema::save(&NOTES, &notes)?;
+    attachments::collect_garbage()?;
+    Ok(())
+}
+
+/// Runs `change` on the attachments of a note and saves it.
+pub fn update_attachments<R>(
+    id: &str,
+    change: impl FnOnce(&mut Vec<Attachment>) -> Result<R, String>,
+) -> Result<R, String> {
+    let mut notes = load_notes()?;
+    let note = find(&mut notes, id)?;
+    let result = change(&mut note.attachments)?;
+    note.updated_at = now();
+    schema::save(&NOTES, &notes)?;
+    Ok(result)
+}
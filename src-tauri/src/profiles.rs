@@ -0,0 +1,240 @@
+// Profiles
+//
+// Every profile keeps its own tasks, events, caches and tokens. The "default"
+// profile lives directly in the local data directory so data written before
+// profiles existed keeps working; other profiles get their own folder under
+// Daspberry/profiles/<name>.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use tauri::command;
+use tauri::Manager;
+
+pub const DEFAULT_PROFILE: &str = "default";
+const REGISTRY_FILE: &str = "profiles.json";
+
+static ACTIVE_PROFILE: Mutex<Option<String>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Profile {
+    name: String,
+    created_at: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct ProfileRegistry {
+    active: String,
+    profiles: Vec<Profile>,
+}
+
+#[derive(Serialize)]
+pub struct ProfileList {
+    active: String,
+    profiles: Vec<Profile>,
+}
+
+/// Root of all Daspberry data, shared by every profile.
+pub fn root_dir() -> Result<PathBuf, String> {
+    dirs::data_local_dir().ok_or_else(|| "Failed to get local data directory".to_string())
+}
+
+fn app_dir() -> Result<PathBuf, String> {
+    Ok(root_dir()?.join("Daspberry"))
+}
+
+fn profile_dir(name: &str) -> Result<PathBuf, String> {
+    if name == DEFAULT_PROFILE {
+        root_dir()
+    } else {
+        Ok(app_dir()?.join("profiles").join(name))
+    }
+}
+
+/// Name of the profile all data commands currently resolve against.
+pub fn active_profile() -> String {
+    ACTIVE_PROFILE
+        .lock()
+        .ok()
+        .and_then(|active| active.clone())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+/// Directory of the active profile, created on first use.
+pub fn data_dir() -> Result<PathBuf, String> {
+    let dir = profile_dir(&active_profile())?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create profile directory: {}", e))?;
+    Ok(dir)
+}
+
+/// Path of a data file inside the active profile.
+pub fn data_file(file_name: &str) -> Result<PathBuf, String> {
+    Ok(data_dir()?.join(file_name))
+}
+
+fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > 64 {
+        return Err("Profile name must be between 1 and 64 characters".to_string());
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err("Profile name may only contain letters, digits, '-' and '_'".to_string());
+    }
+    Ok(())
+}
+
+fn load_registry() -> Result<ProfileRegistry, String> {
+    let path = app_dir()?.join(REGISTRY_FILE);
+
+    let mut registry = if path.exists() {
+        let json = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read profiles file: {}", e))?;
+        serde_json::from_str(&json)
+            .map_err(|e| format!("Failed to deserialize profiles: {}", e))?
+    } else {
+        ProfileRegistry::default()
+    };
+
+    if !registry.profiles.iter().any(|p| p.name == DEFAULT_PROFILE) {
+        registry.profiles.insert(0, Profile {
+            name: DEFAULT_PROFILE.to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        });
+    }
+    if registry.active.is_empty() {
+        registry.active = DEFAULT_PROFILE.to_string();
+    }
+
+    Ok(registry)
+}
+
+fn save_registry(registry: &ProfileRegistry) -> Result<(), String> {
+    let dir = app_dir()?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create app directory: {}", e))?;
+
+    let json = serde_json::to_string_pretty(registry)
+        .map_err(|e| format!("Failed to serialize profiles: {}", e))?;
+    fs::write(dir.join(REGISTRY_FILE), json)
+        .map_err(|e| format!("Failed to write profiles file: {}", e))
+}
+
+fn set_active(name: &str) -> Result<(), String> {
+    let mut active = ACTIVE_PROFILE
+        .lock()
+        .map_err(|_| "Active profile lock poisoned".to_string())?;
+    *active = Some(name.to_string());
+    Ok(())
+}
+
+fn add_profile(registry: &mut ProfileRegistry, name: &str) -> Result<(), String> {
+    validate_name(name)?;
+    if registry.profiles.iter().any(|p| p.name == name) {
+        return Err(format!("Profile '{}' already exists", name));
+    }
+
+    fs::create_dir_all(profile_dir(name)?)
+        .map_err(|e| format!("Failed to create profile directory: {}", e))?;
+    registry.profiles.push(Profile {
+        name: name.to_string(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+    });
+    Ok(())
+}
+
+/// Picks the profile to start with: `--profile <name>` (or `--profile=<name>`)
+/// wins over the last profile that was switched to. A profile named on the
+/// command line is created if it does not exist yet.
+pub fn init_from_args() -> Result<(), String> {
+    let mut args = std::env::args().skip(1);
+    let mut requested = None;
+    while let Some(arg) = args.next() {
+        if arg == "--profile" {
+            requested = args.next();
+        } else if let Some(name) = arg.strip_prefix("--profile=") {
+            requested = Some(name.to_string());
+        }
+    }
+
+    let mut registry = load_registry()?;
+    if let Some(name) = requested {
+        if !registry.profiles.iter().any(|p| p.name == name) {
+            add_profile(&mut registry, &name)?;
+            save_registry(&registry)?;
+        }
+        return set_active(&name);
+    }
+
+    let active = if registry.profiles.iter().any(|p| p.name == registry.active) {
+        registry.active
+    } else {
+        DEFAULT_PROFILE.to_string()
+    };
+    set_active(&active)
+}
+
+#[command]
+pub fn list_profiles() -> Result<ProfileList, String> {
+    let registry = load_registry()?;
+    Ok(ProfileList {
+        active: active_profile(),
+        profiles: registry.profiles,
+    })
+}
+
+#[command]
+pub fn get_active_profile() -> String {
+    active_profile()
+}
+
+#[command]
+pub fn create_profile(name: String) -> Result<Profile, String> {
+    let mut registry = load_registry()?;
+    add_profile(&mut registry, &name)?;
+    save_registry(&registry)?;
+
+    registry
+        .profiles
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| "Failed to create profile".to_string())
+}
+
+#[command]
+pub fn switch_profile(app_handle: tauri::AppHandle, name: String) -> Result<(), String> {
+    let mut registry = load_registry()?;
+    if !registry.profiles.iter().any(|p| p.name == name) {
+        return Err(format!("Profile '{}' does not exist", name));
+    }
+
+    registry.active = name.clone();
+    save_registry(&registry)?;
+    set_active(&name)?;
+
+    app_handle
+        .emit_all("profile-changed", name)
+        .map_err(|e| format!("Failed to emit profile-changed event: {}", e))
+}
+
+#[command]
+pub fn delete_profile(name: String) -> Result<(), String> {
+    if name == DEFAULT_PROFILE {
+        return Err("The default profile cannot be deleted".to_string());
+    }
+    if name == active_profile() {
+        return Err("Switch to another profile before deleting this one".to_string());
+    }
+
+    let mut registry = load_registry()?;
+    let before = registry.profiles.len();
+    registry.profiles.retain(|p| p.name != name);
+    if registry.profiles.len() == before {
+        return Err(format!("Profile '{}' does not exist", name));
+    }
+
+    let dir = profile_dir(&name)?;
+    if dir.exists() {
+        fs::remove_dir_all(&dir).map_err(|e| format!("Failed to remove profile directory: {}", e))?;
+    }
+    save_registry(&registry)
+}
@@ -0,0 +1,338 @@
+// Projects
+//
+// Tasks reference a project by project_id. Task.project still carries the
+// project's name so the UI and Firestore documents stay readable; whenever
+// tasks are loaded or saved, a name without an id is linked to the project of
+// that name, creating it if needed. This is also how task files from before
+// projects existed are migrated. projects.json is not synced, so an id synced
+// from another device always wins over the name.
+
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+use crate::schema::{self, Schema};
+use crate::{tasks, Task};
+
+pub const PROJECTS: Schema = Schema {
+    file: "projects.json",
+    version: 1,
+    migrations: &[schema::wrap_items],
+};
+
+const PALETTE: &[&str] = &["#22d3ee", "#a78bfa", "#f472b6", "#34d399", "#fbbf24", "#f87171", "#60a5fa"];
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Project {
+    pub id: String,
+    pub name: String,
+    pub color: String,
+    pub archived: bool,
+    // Folder as listed by get_project_folders
+    pub folder_path: Option<String>,
+    // "owner/repo"
+    pub github_repo: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+pub fn load_projects() -> Result<Vec<Project>, String> {
+    schema::load(&PROJECTS)
+}
+
+fn new_project(projects: &[Project], name: &str) -> Project {
+    let timestamp = now();
+    Project {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        color: PALETTE[projects.len() % PALETTE.len()].to_string(),
+        created_at: timestamp.clone(),
+        updated_at: timestamp,
+        ..Default::default()
+    }
+}
+
+fn find_by_name<'a>(projects: &'a [Project], name: &str) -> Option<&'a Project> {
+    let mut matches = projects.iter().filter(|p| p.name.eq_ignore_ascii_case(name));
+    let first = matches.next()?;
+    if first.archived {
+        // Prefer an active project when an archived one shares the name
+        return matches.find(|p| !p.archived).or(Some(first));
+    }
+    Some(first)
+}
+
+// Links every task to a project. A task's project_id is trusted: when its
+// name differs from the project's and the task is the newer of the two, the
+// project was renamed on another device and takes the new name; otherwise the
+// task takes the project's name. Tasks without an id are linked by name,
+// creating projects as needed. Returns whether projects and tasks changed.
+fn link(projects: &mut Vec<Project>, tasks: &mut [Task]) -> (bool, bool) {
+    let mut projects_changed = false;
+    let mut tasks_changed = false;
+
+    // Renames first, so every task of a renamed project picks up the new name
+    for task in tasks.iter() {
+        let name = task.project.trim();
+        let Some(project) = task.project_id.as_ref().and_then(|id| projects.iter_mut().find(|p| p.id == *id)) else {
+            continue;
+        };
+        let task_is_newer = task.updated_at.as_deref().is_some_and(|t| t > project.updated_at.as_str());
+        if !name.is_empty() && project.name != name && task_is_newer {
+            project.name = name.to_string();
+            project.updated_at = task.updated_at.clone().unwrap_or_else(now);
+            projects_changed = true;
+        }
+    }
+
+    for task in tasks.iter_mut() {
+        let name = task.project.trim().to_string();
+        if name.is_empty() {
+            if task.project_id.is_some() {
+                task.project_id = None;
+                tasks_changed = true;
+            }
+            continue;
+        }
+
+        if let Some(id) = task.project_id.clone() {
+            match projects.iter().find(|p| p.id == id) {
+                Some(project) if project.name == task.project => {}
+                Some(project) => {
+                    task.project = project.name.clone();
+                    tasks_changed = true;
+                }
+                // Linked to a project this device has never seen (synced from elsewhere)
+                None => {
+                    let mut project = new_project(projects, &name);
+                    project.id = id;
+                    projects.push(project);
+                    projects_changed = true;
+                }
+            }
+            continue;
+        }
+
+        let id = match find_by_name(projects, &name) {
+            Some(project) => project.id.clone(),
+            None => {
+                let project = new_project(projects, &name);
+                let id = project.id.clone();
+                projects.push(project);
+                projects_changed = true;
+                id
+            }
+        };
+        task.project_id = Some(id);
+        task.project = name;
+        tasks_changed = true;
+    }
+
+    (projects_changed, tasks_changed)
+}
+
+/// Links tasks that are about to be saved to their projects and saves any
+/// project that was created or renamed.
+pub fn link_tasks(tasks: &mut [Task]) -> Result<(), String> {
+    let mut projects = load_projects()?;
+    let (projects_changed, _) = link(&mut projects, tasks);
+    if projects_changed {
+        schema::save(&PROJECTS, &projects)?;
+    }
+    Ok(())
+}
+
+/// Links loaded tasks to their projects without writing anything. Projects
+/// this creates are saved along with the tasks the next time they are saved.
+pub fn link_loaded_tasks(tasks: &mut [Task]) -> Result<(), String> {
+    let mut projects = load_projects()?;
+    link(&mut projects, tasks);
+    Ok(())
+}
+
+// Renames a project and rewrites the name carried by its tasks.
+fn apply_rename(projects: &mut [Project], id: &str, name: &str) -> Result<Vec<Task>, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Project name cannot be empty".to_string());
+    }
+    if projects.iter().any(|p| p.id != id && p.name.eq_ignore_ascii_case(name)) {
+        return Err(format!("A project named '{}' already exists", name));
+    }
+
+    let project = projects
+        .iter_mut()
+        .find(|p| p.id == id)
+        .ok_or_else(|| format!("Project '{}' does not exist", id))?;
+    let mut tasks: Vec<Task> = schema::load(&schema::TASKS)?;
+    if project.name == name {
+        return Ok(tasks);
+    }
+
+    let timestamp = now();
+    project.name = name.to_string();
+    project.updated_at = timestamp.clone();
+
+    for task in tasks.iter_mut().filter(|t| t.project_id.as_deref() == Some(id)) {
+        task.project = name.to_string();
+        task.updated_at = Some(timestamp.clone());
+    }
+    tasks::store(&tasks)?;
+    Ok(tasks)
+}
+
+/// The project linked to a folder from get_project_folders, if any.
+pub fn project_for_folder(projects: &[Project], folder: &Path) -> Option<String> {
+    projects
+        .iter()
+        .find(|p| p.folder_path.as_deref().map(Path::new) == Some(folder))
+        .map(|p| p.id.clone())
+}
+
+#[command]
+pub fn list_projects(include_archived: Option<bool>) -> Result<Vec<Project>, String> {
+    let include_archived = include_archived.unwrap_or(false);
+    Ok(load_projects()?
+        .into_iter()
+        .filter(|p| include_archived || !p.archived)
+        .collect())
+}
+
+#[command]
+pub fn create_project(
+    name: String,
+    color: Option<String>,
+    folder_path: Option<String>,
+    github_repo: Option<String>,
+) -> Result<Project, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Project name cannot be empty".to_string());
+    }
+
+    let mut projects = load_projects()?;
+    if projects.iter().any(|p| p.name.eq_ignore_ascii_case(name)) {
+        return Err(format!("A project named '{}' already exists", name));
+    }
+
+    let mut project = new_project(&projects, name);
+    if let Some(color) = color {
+        project.color = color;
+    }
+    project.folder_path = folder_path;
+    project.github_repo = github_repo;
+
+    projects.push(project.clone());
+    schema::save(&PROJECTS, &projects)?;
+    Ok(project)
+}
+
+#[command]
+pub fn update_project(project: Project) -> Result<Project, String> {
+    let mut projects = load_projects()?;
+    apply_rename(&mut projects, &project.id, &project.name)?;
+
+    let existing = projects
+        .iter_mut()
+        .find(|p| p.id == project.id)
+        .ok_or_else(|| format!("Project '{}' does not exist", project.id))?;
+    existing.color = project.color;
+    existing.archived = project.archived;
+    existing.folder_path = project.folder_path.filter(|f| !f.trim().is_empty());
+    existing.github_repo = project.github_repo.filter(|r| !r.trim().is_empty());
+    existing.updated_at = now();
+
+    let updated = existing.clone();
+    schema::save(&PROJECTS, &projects)?;
+    Ok(updated)
+}
+
+#[command]
+pub fn rename_project(id: String, name: String) -> Result<Vec<Task>, String> {
+    let mut projects = load_projects()?;
+    let tasks = apply_rename(&mut projects, &id, &name)?;
+    schema::save(&PROJECTS, &projects)?;
+    Ok(tasks)
+}
+
+/// Deletes a project and detaches its tasks. Returns the updated tasks.
+#[command]
+pub fn delete_project(id: String) -> Result<Vec<Task>, String> {
+    let mut projects = load_projects()?;
+    let before = projects.len();
+    projects.retain(|p| p.id != id);
+    if projects.len() == before {
+        return Err(format!("Project '{}' does not exist", id));
+    }
+
+    let mut tasks: Vec<Task> = schema::load(&schema::TASKS)?;
+    let timestamp = now();
+    for task in tasks.iter_mut().filter(|t| t.project_id.as_deref() == Some(id.as_str())) {
+        task.project_id = None;
+        task.project = String::new();
+        task.updated_at = Some(timestamp.clone());
+    }
+
+    tasks::store(&tasks)?;
+    schema::save(&PROJECTS, &projects)?;
+    Ok(tasks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: &str, project: &str, project_id: Option<&str>, updated_at: &str) -> Task {
+        Task {
+            id: id.to_string(),
+            project: project.to_string(),
+            project_id: project_id.map(String::from),
+            updated_at: Some(updated_at.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn links_by_name_and_reuses_projects() {
+        let mut projects = Vec::new();
+        let mut tasks = vec![task("1", "Work", None, "2024-05-01T00:00:00.000Z"), task("2", "work", None, "2024-05-01T00:00:00.000Z")];
+
+        assert_eq!(link(&mut projects, &mut tasks), (true, true));
+        assert_eq!(projects.len(), 1);
+        assert_eq!(tasks[0].project_id, tasks[1].project_id);
+    }
+
+    #[test]
+    fn takes_renames_from_other_devices() {
+        let mut projects = vec![Project {
+            id: "p".to_string(),
+            name: "Old".to_string(),
+            updated_at: "2024-05-01T00:00:00.000Z".to_string(),
+            ..Default::default()
+        }];
+        let mut tasks = vec![
+            task("1", "Old", Some("p"), "2024-04-01T00:00:00.000Z"),
+            task("2", "New", Some("p"), "2024-05-02T00:00:00.000Z"),
+        ];
+
+        link(&mut projects, &mut tasks);
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].name, "New");
+        assert!(tasks.iter().all(|t| t.project == "New" && t.project_id.as_deref() == Some("p")));
+    }
+
+    #[test]
+    fn keeps_ids_of_unknown_projects() {
+        let mut projects = Vec::new();
+        let mut tasks = vec![task("1", "Remote", Some("remote-id"), "2024-05-01T00:00:00.000Z")];
+
+        link(&mut projects, &mut tasks);
+        assert_eq!(projects[0].id, "remote-id");
+        assert_eq!(tasks[0].project_id.as_deref(), Some("remote-id"));
+    }
+}
@@ -0,0 +1,479 @@
+// Travel reminders
+//
+// Estimates how long it takes to get from a configured origin (home, work,
+// ...) to an event's coordinates and emits "leave-now" once it is time to set
+// off for time_start. Travel time comes from an Estimator: a straight-line
+// heuristic by default, or an OSRM-compatible routing service, falling back
+// to the heuristic when routing fails. A routing service that keeps failing
+// is left alone for a while, backing off up to an hour. get_travel_plan
+// returns the plan with the reasoning behind it for the UI.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Instant;
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+use tauri::{command, Manager};
+
+use crate::geocoding::{distance_meters, valid_coordinates};
+use crate::{storage, timezones, Event};
+
+const SETTINGS_FILE: &str = "travel_settings.json";
+const CHECK_INTERVAL_SECS: u64 = 60;
+// Events starting further ahead than this are not checked by the scheduler
+const LOOKAHEAD_HOURS: i64 = 12;
+const MAX_BUFFER_MINUTES: i64 = 24 * 60;
+const MAX_DETOUR_FACTOR: f64 = 10.0;
+// Longer trips are not worth a reminder and would overflow the time arithmetic
+const MAX_TRAVEL_MINUTES: i64 = 7 * 24 * 60;
+const ROUTING_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+const ROUTING_BACKOFF_SECS: u64 = 60;
+const MAX_ROUTING_BACKOFF_SECS: u64 = 60 * 60;
+
+// Events already reminded about, keyed by id and start time so moving an
+// event re-arms its reminder
+static REMINDED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+// Routed travel times by origin and destination, so the scheduler does not
+// query the routing service every minute
+static ROUTES: Mutex<Option<HashMap<String, Estimate>>> = Mutex::new(None);
+// Routing services that failed, keyed by URL
+static ROUTING_FAILURES: Mutex<Option<HashMap<String, Backoff>>> = Mutex::new(None);
+
+type EstimateFuture<'a> = Pin<Box<dyn Future<Output = Result<Estimate, String>> + Send + 'a>>;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EstimatorKind {
+    StraightLine,
+    Routing,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Origin {
+    name: String,
+    latitude: f64,
+    longitude: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TravelSettings {
+    enabled: bool,
+    origins: Vec<Origin>,
+    // Name of the origin reminders are computed from; the first one if unset
+    active_origin: Option<String>,
+    estimator: EstimatorKind,
+    // Straight-line heuristic: average speed, and how much longer the real
+    // route is than the straight line
+    average_speed_kmh: f64,
+    detour_factor: f64,
+    // OSRM-compatible service, e.g. https://router.project-osrm.org
+    routing_url: Option<String>,
+    routing_profile: String,
+    // Extra time to arrive early, park, find the room...
+    buffer_minutes: i64,
+}
+
+impl Default for TravelSettings {
+    fn default() -> Self {
+        TravelSettings {
+            enabled: true,
+            origins: Vec::new(),
+            active_origin: None,
+            estimator: EstimatorKind::StraightLine,
+            average_speed_kmh: 30.0,
+            detour_factor: 1.3,
+            routing_url: None,
+            routing_profile: "driving".to_string(),
+            buffer_minutes: 10,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Method {
+    StraightLine,
+    Routing,
+}
+
+#[derive(Clone)]
+struct Estimate {
+    method: Method,
+    distance_km: f64,
+    travel_minutes: i64,
+}
+
+struct Backoff {
+    failures: u32,
+    retry_at: Instant,
+}
+
+#[derive(Serialize, Clone)]
+pub struct TravelPlan {
+    event_id: String,
+    title: String,
+    origin: String,
+    method: Method,
+    distance_km: f64,
+    travel_minutes: i64,
+    buffer_minutes: i64,
+    starts_at: String,
+    leave_at: String,
+    // Negative once the time to leave has passed
+    minutes_until_leave: i64,
+    reasoning: Vec<String>,
+}
+
+trait Estimator: Send + Sync {
+    fn estimate<'a>(&'a self, from: (f64, f64), to: (f64, f64)) -> EstimateFuture<'a>;
+}
+
+struct StraightLine {
+    speed_kmh: f64,
+    detour_factor: f64,
+}
+
+impl StraightLine {
+    fn estimate_now(&self, from: (f64, f64), to: (f64, f64)) -> Estimate {
+        let distance_km = distance_meters(from.0, from.1, to.0, to.1) / 1000.0;
+        let hours = distance_km * self.detour_factor / self.speed_kmh;
+        Estimate {
+            method: Method::StraightLine,
+            distance_km,
+            travel_minutes: ((hours * 60.0).ceil() as i64).min(MAX_TRAVEL_MINUTES),
+        }
+    }
+}
+
+impl Estimator for StraightLine {
+    fn estimate<'a>(&'a self, from: (f64, f64), to: (f64, f64)) -> EstimateFuture<'a> {
+        let estimate = self.estimate_now(from, to);
+        Box::pin(async move { Ok(estimate) })
+    }
+}
+
+struct Osrm {
+    client: reqwest::Client,
+    base_url: String,
+    profile: String,
+}
+
+#[derive(Deserialize)]
+struct OsrmResponse {
+    code: String,
+    #[serde(default)]
+    routes: Vec<OsrmRoute>,
+}
+
+#[derive(Deserialize)]
+struct OsrmRoute {
+    // Seconds and meters
+    duration: f64,
+    distance: f64,
+}
+
+impl Estimator for Osrm {
+    fn estimate<'a>(&'a self, from: (f64, f64), to: (f64, f64)) -> EstimateFuture<'a> {
+        Box::pin(async move {
+            // OSRM takes longitude first
+            let url = format!(
+                "{}/route/v1/{}/{},{};{},{}",
+                self.base_url.trim_end_matches('/'),
+                self.profile,
+                from.1,
+                from.0,
+                to.1,
+                to.0
+            );
+            let response: OsrmResponse = self
+                .client
+                .get(&url)
+                .query(&[("overview", "false")])
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .map_err(|e| format!("Routing request failed: {}", e))?
+                .json()
+                .await
+                .map_err(|e| format!("Failed to parse routing response: {}", e))?;
+            let route = response
+                .routes
+                .first()
+                .filter(|_| response.code == "Ok")
+                .ok_or_else(|| format!("No route found ({})", response.code))?;
+            Ok(Estimate {
+                method: Method::Routing,
+                distance_km: route.distance / 1000.0,
+                travel_minutes: ((route.duration / 60.0).ceil() as i64).min(MAX_TRAVEL_MINUTES),
+            })
+        })
+    }
+}
+
+fn load_settings() -> Result<TravelSettings, String> {
+    match storage::read_to_string(SETTINGS_FILE)
+        .map_err(|e| format!("Failed to read travel settings: {}", e))?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| format!("Failed to deserialize travel settings: {}", e)),
+        None => Ok(TravelSettings::default()),
+    }
+}
+
+fn origin<'a>(settings: &'a TravelSettings, name: Option<&str>) -> Result<&'a Origin, String> {
+    match name.or(settings.active_origin.as_deref()) {
+        Some(name) => settings
+            .origins
+            .iter()
+            .find(|o| o.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("No origin named '{}'", name)),
+        None => settings
+            .origins
+            .first()
+            .ok_or_else(|| "No home or work location is configured".to_string()),
+    }
+}
+
+/// When the event starts, in the user's time.
+fn starts_at(event: &Event) -> Option<DateTime<Local>> {
+    timezones::event_start(event).map(|start| start.with_timezone(&Local))
+}
+
+fn destination(event: &Event) -> Option<(f64, f64)> {
+    match (event.latitude, event.longitude) {
+        (Some(lat), Some(lon)) if valid_coordinates(lat, lon) => Some((lat, lon)),
+        _ => None,
+    }
+}
+
+async fn estimate(settings: &TravelSettings, from: (f64, f64), to: (f64, f64), reasoning: &mut Vec<String>) -> Result<Estimate, String> {
+    let straight_line = StraightLine {
+        speed_kmh: settings.average_speed_kmh.max(1.0),
+        detour_factor: settings.detour_factor.clamp(1.0, MAX_DETOUR_FACTOR),
+    };
+    let heuristic = |reasoning: &mut Vec<String>| {
+        let estimate = straight_line.estimate_now(from, to);
+        reasoning.push(format!(
+            "{:.1} km in a straight line, times {:.2} for detours, at {:.0} km/h takes about {} min",
+            estimate.distance_km, straight_line.detour_factor, straight_line.speed_kmh, estimate.travel_minutes
+        ));
+        estimate
+    };
+
+    let base_url = match (settings.estimator, &settings.routing_url) {
+        (EstimatorKind::Routing, Some(url)) if !url.trim().is_empty() => url.clone(),
+        (EstimatorKind::Routing, _) => {
+            reasoning.push("Routing is selected but no routing URL is configured".to_string());
+            return Ok(heuristic(reasoning));
+        }
+        (EstimatorKind::StraightLine, _) => return Ok(heuristic(reasoning)),
+    };
+
+    let key = format!(
+        "{}|{}|{:.5},{:.5}|{:.5},{:.5}",
+        base_url, settings.routing_profile, from.0, from.1, to.0, to.1
+    );
+    let cached = ROUTES
+        .lock()
+        .map_err(|_| "Route cache lock poisoned".to_string())?
+        .as_ref()
+        .and_then(|routes| routes.get(&key).cloned());
+    let backing_off = ROUTING_FAILURES
+        .lock()
+        .map_err(|_| "Routing backoff lock poisoned".to_string())?
+        .as_ref()
+        .and_then(|failures| failures.get(&base_url))
+        .is_some_and(|backoff| backoff.retry_at > Instant::now());
+    let routed = match cached {
+        Some(estimate) => Ok(estimate),
+        None if backing_off => Err("the routing service failed recently".to_string()),
+        None => {
+            let result = match reqwest::Client::builder().timeout(ROUTING_TIMEOUT).build() {
+                Ok(client) => {
+                    let osrm = Osrm {
+                        client,
+                        base_url: base_url.clone(),
+                        profile: settings.routing_profile.clone(),
+                    };
+                    osrm.estimate(from, to).await
+                }
+                Err(e) => Err(format!("Failed to create routing client: {}", e)),
+            };
+            let mut failures = ROUTING_FAILURES
+                .lock()
+                .map_err(|_| "Routing backoff lock poisoned".to_string())?;
+            let failures = failures.get_or_insert_with(HashMap::new);
+            match &result {
+                Ok(estimate) => {
+                    failures.remove(&base_url);
+                    ROUTES
+                        .lock()
+                        .map_err(|_| "Route cache lock poisoned".to_string())?
+                        .get_or_insert_with(HashMap::new)
+                        .insert(key, estimate.clone());
+                }
+                Err(_) => {
+                    let backoff = failures.entry(base_url).or_insert(Backoff {
+                        failures: 0,
+                        retry_at: Instant::now(),
+                    });
+                    backoff.failures += 1;
+                    let secs = ROUTING_BACKOFF_SECS
+                        .saturating_mul(1 << (backoff.failures - 1).min(6))
+                        .min(MAX_ROUTING_BACKOFF_SECS);
+                    backoff.retry_at = Instant::now() + std::time::Duration::from_secs(secs);
+                }
+            }
+            result
+        }
+    };
+
+    Ok(match routed {
+        Ok(estimate) => {
+            reasoning.push(format!(
+                "The {} route is {:.1} km and takes about {} min",
+                settings.routing_profile, estimate.distance_km, estimate.travel_minutes
+            ));
+            estimate
+        }
+        Err(e) => {
+            reasoning.push(format!("Routing failed ({}), using the straight-line estimate", e));
+            heuristic(reasoning)
+        }
+    })
+}
+
+async fn plan(settings: &TravelSettings, event: &Event, origin: &Origin, now: DateTime<Local>) -> Result<TravelPlan, String> {
+    let start = starts_at(event).ok_or_else(|| format!("'{}' has no start date and time", event.title))?;
+    let to = destination(event).ok_or_else(|| format!("'{}' has no coordinates", event.title))?;
+
+    let mut reasoning = vec![format!(
+        "'{}' starts at {}",
+        event.title,
+        start.format("%Y-%m-%d %H:%M")
+    )];
+    reasoning.push(format!("Travelling from {}", origin.name));
+    let estimate = estimate(settings, (origin.latitude, origin.longitude), to, &mut reasoning).await?;
+
+    let buffer = settings.buffer_minutes.clamp(0, MAX_BUFFER_MINUTES);
+    let leave_at = start - Duration::minutes(estimate.travel_minutes + buffer);
+    reasoning.push(format!(
+        "Adding {} min of buffer, leave at {}",
+        buffer,
+        leave_at.format("%H:%M")
+    ));
+
+    Ok(TravelPlan {
+        event_id: event.id.clone(),
+        title: event.title.clone(),
+        origin: origin.name.clone(),
+        method: estimate.method,
+        distance_km: estimate.distance_km,
+        travel_minutes: estimate.travel_minutes,
+        buffer_minutes: buffer,
+        starts_at: start.to_rfc3339(),
+        leave_at: leave_at.to_rfc3339(),
+        minutes_until_leave: (leave_at - now).num_minutes(),
+        reasoning,
+    })
+}
+
+// Plans for events starting within the lookahead window whose time to leave
+// has come and that were not reminded about yet.
+async fn due_reminders() -> Result<Vec<TravelPlan>, String> {
+    let settings = load_settings()?;
+    if !settings.enabled || settings.origins.is_empty() {
+        return Ok(Vec::new());
+    }
+    let origin = origin(&settings, None)?.clone();
+    let now = Local::now();
+
+    let mut due = Vec::new();
+    for event in crate::load_local_events()? {
+        let Some(start) = starts_at(&event) else { continue };
+        if destination(&event).is_none() || start <= now || start > now + Duration::hours(LOOKAHEAD_HOURS) {
+            continue;
+        }
+        let key = format!("{}|{}", event.id, start.to_rfc3339());
+        let reminded = REMINDED
+            .lock()
+            .map_err(|_| "Reminder lock poisoned".to_string())?
+            .as_ref()
+            .is_some_and(|r| r.contains(&key));
+        if reminded {
+            continue;
+        }
+
+        let plan = plan(&settings, &event, &origin, now).await?;
+        if plan.minutes_until_leave <= 0 {
+            REMINDED
+                .lock()
+                .map_err(|_| "Reminder lock poisoned".to_string())?
+                .get_or_insert_with(HashSet::new)
+                .insert(key);
+            due.push(plan);
+        }
+    }
+    Ok(due)
+}
+
+pub fn start_scheduler(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match due_reminders().await {
+                Ok(plans) => {
+                    for plan in plans {
+                        if let Err(e) = app_handle.emit_all("leave-now", plan) {
+                            eprintln!("Failed to emit leave-now event: {}", e);
+                        }
+                    }
+                }
+                Err(e) => eprintln!("Travel reminder check failed: {}", e),
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+        }
+    });
+}
+
+/// When to leave for an event, with the steps that led there.
+#[command]
+pub async fn get_travel_plan(event_id: String, origin_name: Option<String>) -> Result<TravelPlan, String> {
+    let settings = load_settings()?;
+    let origin = origin(&settings, origin_name.as_deref())?.clone();
+    let event = crate::load_local_events()?
+        .into_iter()
+        .find(|e| e.id == event_id)
+        .ok_or_else(|| format!("Event '{}' does not exist", event_id))?;
+    plan(&settings, &event, &origin, Local::now()).await
+}
+
+#[command]
+pub fn get_travel_settings() -> Result<TravelSettings, String> {
+    load_settings()
+}
+
+#[command]
+pub fn save_travel_settings(mut settings: TravelSettings) -> Result<(), String> {
+    if let Some(origin) = settings.origins.iter().find(|o| !valid_coordinates(o.latitude, o.longitude)) {
+        return Err(format!("'{}' does not have valid coordinates", origin.name));
+    }
+    if !(settings.average_speed_kmh.is_finite() && settings.average_speed_kmh > 0.0) {
+        return Err("The average speed must be positive".to_string());
+    }
+    if !settings.detour_factor.is_finite() {
+        return Err("The detour factor must be a number".to_string());
+    }
+    settings.detour_factor = settings.detour_factor.clamp(1.0, MAX_DETOUR_FACTOR);
+    settings.buffer_minutes = settings.buffer_minutes.clamp(0, MAX_BUFFER_MINUTES);
+    let json = serde_json::to_string(&settings)
+        .map_err(|e| format!("Failed to serialize travel settings: {}", e))?;
+    storage::write(SETTINGS_FILE, json.as_bytes())
+        .map_err(|e| format!("Failed to write travel settings: {}", e))?;
+    // Origins or the routing service may have changed
+    *ROUTES.lock().map_err(|_| "Route cache lock poisoned".to_string())? = None;
+    *ROUTING_FAILURES.lock().map_err(|_| "Routing backoff lock poisoned".to_string())? = None;
+    Ok(())
+}
@@ -0,0 +1,303 @@
+// Scheduling assistant
+//
+// Finds free slots of a given length within working hours by subtracting the
+// merged local and Google events, each padded by a buffer, from every working
+// day in a date range. Other attendees' calendars can be added through
+// Google's freeBusy endpoint. The UI uses the slots to suggest when to work
+// on a task, taking the duration from its estimate.
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveTime, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::command;
+
+use crate::{conflicts, timezones};
+use crate::tasks::parse_bound;
+
+const FREE_BUSY_URL: &str = "https://www.googleapis.com/calendar/v3/freeBusy";
+// Searches without an end date look this far ahead
+const DEFAULT_RANGE_DAYS: i64 = 7;
+// Slots starting today start no earlier than the next quarter hour
+const ROUND_TO_SECS: i64 = 15 * 60;
+// Longest slot, buffer and search range accepted
+const MAX_MINUTES: i64 = 7 * 24 * 60;
+const MAX_RANGE_DAYS: i64 = 366;
+
+type Busy = (DateTime<FixedOffset>, DateTime<FixedOffset>);
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SlotOptions {
+    // Inclusive, YYYY-MM-DD; from defaults to today
+    from: Option<String>,
+    to: Option<String>,
+    // Defaults to the task's estimate when task_id is given
+    duration_minutes: Option<i64>,
+    task_id: Option<String>,
+    work_start: String,
+    work_end: String,
+    // ISO weekdays, Monday = 1
+    work_days: Vec<u32>,
+    // Kept free before and after every event
+    buffer_minutes: i64,
+    include_all_day: bool,
+    // Other people's calendars to check through Google freeBusy
+    attendees: Vec<String>,
+    access_token: Option<String>,
+    max_slots: usize,
+}
+
+impl Default for SlotOptions {
+    fn default() -> Self {
+        SlotOptions {
+            from: None,
+            to: None,
+            duration_minutes: None,
+            task_id: None,
+            work_start: "09:00".to_string(),
+            work_end: "17:00".to_string(),
+            work_days: vec![1, 2, 3, 4, 5],
+            buffer_minutes: 10,
+            include_all_day: false,
+            attendees: Vec::new(),
+            access_token: None,
+            max_slots: 20,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Slot {
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+    minutes: i64,
+}
+
+#[derive(Deserialize)]
+struct FreeBusyResponse {
+    #[serde(default)]
+    calendars: std::collections::HashMap<String, FreeBusyCalendar>,
+}
+
+#[derive(Deserialize)]
+struct FreeBusyCalendar {
+    #[serde(default)]
+    busy: Vec<FreeBusyPeriod>,
+    #[serde(default)]
+    errors: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct FreeBusyPeriod {
+    start: String,
+    end: String,
+}
+
+fn parse_work_time(time: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| format!("Invalid working hour '{}'", time))
+}
+
+fn local(date: NaiveDate, time: NaiveTime) -> Option<DateTime<FixedOffset>> {
+    timezones::resolve(timezones::user_zone(), date.and_time(time)).map(|d| d.fixed_offset())
+}
+
+async fn attendee_busy(
+    attendees: &[String],
+    access_token: &str,
+    from: DateTime<FixedOffset>,
+    to: DateTime<FixedOffset>,
+) -> Result<Vec<Busy>, String> {
+    let items: Vec<_> = attendees.iter().map(|id| json!({ "id": id })).collect();
+    let response: FreeBusyResponse = reqwest::Client::new()
+        .post(FREE_BUSY_URL)
+        .bearer_auth(access_token)
+        .json(&json!({
+            "timeMin": from.to_rfc3339(),
+            "timeMax": to.to_rfc3339(),
+            "items": items,
+        }))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Failed to query free/busy: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse free/busy response: {}", e))?;
+
+    let mut busy = Vec::new();
+    for (id, calendar) in response.calendars {
+        if !calendar.errors.is_empty() {
+            return Err(format!("Free/busy is not available for {}", id));
+        }
+        for period in calendar.busy {
+            let start = DateTime::parse_from_rfc3339(&period.start);
+            let end = DateTime::parse_from_rfc3339(&period.end);
+            if let (Ok(start), Ok(end)) = (start, end) {
+                busy.push((start, end));
+            }
+        }
+    }
+    Ok(busy)
+}
+
+// Widens a busy period by the buffer on both sides.
+fn pad((start, end): Busy, buffer: Duration) -> Busy {
+    (
+        start.checked_sub_signed(buffer).unwrap_or(start),
+        end.checked_add_signed(buffer).unwrap_or(end),
+    )
+}
+
+// Free stretches of at least `length` in `window`, given busy periods sorted by start.
+fn gaps(window: Busy, busy: &[Busy], length: Duration) -> Vec<Busy> {
+    let (mut cursor, end) = window;
+    let mut free = Vec::new();
+    for &(busy_start, busy_end) in busy {
+        if busy_end <= cursor {
+            continue;
+        }
+        if busy_start >= end {
+            break;
+        }
+        if busy_start - cursor >= length {
+            free.push((cursor, busy_start));
+        }
+        cursor = cursor.max(busy_end);
+    }
+    if end - cursor >= length {
+        free.push((cursor, end));
+    }
+    free
+}
+
+/// Free slots within working hours long enough for the requested duration.
+#[command]
+pub async fn find_free_slots(options: Option<SlotOptions>) -> Result<Vec<Slot>, String> {
+    let options = options.unwrap_or_default();
+
+    let minutes = match (options.duration_minutes, &options.task_id) {
+        (Some(minutes), _) => minutes,
+        (None, Some(task_id)) => crate::load_local_tasks()?
+            .into_iter()
+            .find(|t| t.id == *task_id)
+            .ok_or_else(|| format!("Task '{}' does not exist", task_id))?
+            .estimated_minutes
+            .ok_or_else(|| "The task has no time estimate; give a duration".to_string())?
+            as i64,
+        (None, None) => return Err("A duration or a task is required".to_string()),
+    };
+    if minutes <= 0 {
+        return Err("The duration must be positive".to_string());
+    }
+    if minutes > MAX_MINUTES {
+        return Err("The duration must be at most 7 days".to_string());
+    }
+    let length = Duration::minutes(minutes);
+
+    let work_start = parse_work_time(&options.work_start)?;
+    let work_end = parse_work_time(&options.work_end)?;
+    if work_end <= work_start {
+        return Err("Working hours must end after they start".to_string());
+    }
+
+    let now = Utc::now().with_timezone(&timezones::user_zone()).fixed_offset();
+    let now = now.with_nanosecond(0).unwrap_or(now);
+    let now = now + Duration::seconds((ROUND_TO_SECS - now.timestamp().rem_euclid(ROUND_TO_SECS)) % ROUND_TO_SECS);
+    let from = parse_bound(&options.from)?.unwrap_or(now.date_naive());
+    let to = match parse_bound(&options.to)? {
+        Some(to) => to,
+        None => from
+            .checked_add_signed(Duration::days(DEFAULT_RANGE_DAYS - 1))
+            .ok_or("Invalid start date")?,
+    };
+    if to < from {
+        return Err("The range ends before it starts".to_string());
+    }
+    if (to - from).num_days() >= MAX_RANGE_DAYS {
+        return Err(format!("The range can span at most {} days", MAX_RANGE_DAYS));
+    }
+
+    let buffer = Duration::minutes(options.buffer_minutes.clamp(0, MAX_MINUTES));
+    let mut busy: Vec<Busy> = conflicts::intervals(Some(from), Some(to), true, options.include_all_day)?
+        .into_iter()
+        .map(|i| pad((i.start, i.end), buffer))
+        .collect();
+
+    if !options.attendees.is_empty() {
+        let token = options
+            .access_token
+            .as_deref()
+            .ok_or_else(|| "Checking attendees needs a Google access token".to_string())?;
+        let range_start = local(from, NaiveTime::MIN).ok_or("Invalid start date")?;
+        let range_end = to
+            .succ_opt()
+            .and_then(|day| local(day, NaiveTime::MIN))
+            .ok_or("Invalid end date")?;
+        busy.extend(
+            attendee_busy(&options.attendees, token, range_start, range_end)
+                .await?
+                .into_iter()
+                .map(|period| pad(period, buffer)),
+        );
+    }
+    busy.sort();
+
+    let mut slots = Vec::new();
+    let mut day = from;
+    while day <= to && slots.len() < options.max_slots {
+        if options.work_days.contains(&day.weekday().number_from_monday()) {
+            if let (Some(start), Some(end)) = (local(day, work_start), local(day, work_end)) {
+                let window = (start.max(now), end);
+                if window.0 < window.1 {
+                    slots.extend(gaps(window, &busy, length).into_iter().map(|(start, end)| Slot {
+                        start,
+                        end,
+                        minutes: (end - start).num_minutes(),
+                    }));
+                }
+            }
+        }
+        match day.succ_opt() {
+            Some(next) => day = next,
+            None => break,
+        }
+    }
+    slots.truncate(options.max_slots);
+    Ok(slots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(&format!("2024-05-10T{}:00+02:00", time)).unwrap()
+    }
+
+    #[test]
+    fn finds_gaps_long_enough() {
+        let busy = vec![
+            (at("08:00"), at("09:30")),
+            (at("10:00"), at("11:00")),
+            (at("10:30"), at("12:00")),
+            (at("16:30"), at("18:00")),
+        ];
+        let free = gaps((at("09:00"), at("17:00")), &busy, Duration::minutes(60));
+        // 09:30-10:00 is too short
+        assert_eq!(free, vec![(at("12:00"), at("16:30"))]);
+    }
+
+    #[test]
+    fn keeps_the_whole_window_when_nothing_is_busy() {
+        let free = gaps((at("09:00"), at("17:00")), &[], Duration::minutes(30));
+        assert_eq!(free, vec![(at("09:00"), at("17:00"))]);
+    }
+
+    #[test]
+    fn pads_busy_periods_without_overflowing() {
+        let period = (at("10:00"), at("11:00"));
+        assert_eq!(pad(period, Duration::minutes(15)), (at("09:45"), at("11:15")));
+        let far = (DateTime::<FixedOffset>::MAX_UTC.fixed_offset(), DateTime::<FixedOffset>::MAX_UTC.fixed_offset());
+        assert_eq!(pad(far, Duration::minutes(15)).1, far.1);
+    }
+}
@@ -0,0 +1,266 @@
+// Schema versioning
+//
+// Tasks and events are stored as {"version": N, "items": [...]}. Files written
+// before versioning existed are a bare JSON array and count as version 0.
+// Loading an older file runs the migrations one version at a time, keeps a
+// copy of the original next to it and writes the upgraded file back.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::storage;
+
+// migrations[n] upgrades a version n document to version n + 1
+pub type Migration = fn(Value) -> Result<Value, String>;
+
+pub struct Schema {
+    pub file: &'static str,
+    pub version: u32,
+    pub migrations: &'static [Migration],
+}
+
+pub const TASKS: Schema = Schema {
+    file: "local_tasks_cache.json",
+    version: 2,
+    migrations: &[wrap_items, add_project_ids],
+};
+
+// Archived tasks are tasks with an archived_at, so they share the task migrations
+pub const ARCHIVED_TASKS: Schema = Schema {
+    file: "task_archive.json",
+    version: 2,
+    migrations: &[wrap_items, add_project_ids],
+};
+
+pub const EVENTS: Schema = Schema {
+    file: "local_events_cache.json",
+    version: 2,
+    migrations: &[wrap_items, add_event_timezones],
+};
+
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    version: u32,
+    items: &'a [T],
+}
+
+#[derive(Deserialize)]
+struct OwnedEnvelope<T> {
+    items: Vec<T>,
+}
+
+// v0 -> v1: bare array into a versioned envelope
+pub fn wrap_items(value: Value) -> Result<Value, String> {
+    match value {
+        Value::Array(items) => Ok(json!({ "version": 1, "items": items })),
+        _ => Err("Expected a JSON array".to_string()),
+    }
+}
+
+// Tasks v1 -> v2: tasks reference projects by id. The ids themselves are
+// filled in from the project names by projects::link_tasks.
+fn add_project_ids(mut value: Value) -> Result<Value, String> {
+    let items = value["items"]
+        .as_array_mut()
+        .ok_or_else(|| "Expected an items array".to_string())?;
+    for item in items.iter_mut() {
+        if let Some(task) = item.as_object_mut() {
+            task.entry("project_id").or_insert(Value::Null);
+        }
+    }
+    value["version"] = json!(2);
+    Ok(value)
+}
+
+// Events v1 -> v2: events name the zone their times are in. Existing times
+// were entered as wall-clock times on this machine, so they are in its zone.
+// When that zone cannot be determined they are left without one and keep
+// following the user's zone.
+fn add_event_timezones(mut value: Value) -> Result<Value, String> {
+    let Some(zone) = crate::timezones::system_zone() else {
+        value["version"] = json!(2);
+        return Ok(value);
+    };
+    let items = value["items"]
+        .as_array_mut()
+        .ok_or_else(|| "Expected an items array".to_string())?;
+    for item in items.iter_mut() {
+        if let Some(event) = item.as_object_mut() {
+            if !event.get("timezone").is_some_and(Value::is_string) {
+                event.insert("timezone".to_string(), json!(zone.name()));
+            }
+        }
+    }
+    value["version"] = json!(2);
+    Ok(value)
+}
+
+fn version_of(value: &Value) -> Result<u32, String> {
+    match value {
+        Value::Array(_) => Ok(0),
+        Value::Object(map) => map
+            .get("version")
+            .and_then(Value::as_u64)
+            .map(|v| v as u32)
+            .ok_or_else(|| "Missing schema version".to_string()),
+        _ => Err("Unrecognized data format".to_string()),
+    }
+}
+
+/// Upgrades a document to the schema's current version. Returns the upgraded
+/// document and the version it started at.
+pub fn migrate(schema: &Schema, mut value: Value) -> Result<(Value, u32), String> {
+    let original = version_of(&value)?;
+    if original > schema.version {
+        return Err(format!(
+            "{} has schema version {}, but this build only understands up to {}",
+            schema.file, original, schema.version
+        ));
+    }
+
+    for (step, migration) in schema.migrations.iter().enumerate().skip(original as usize) {
+        value = migration(value).map_err(|e| {
+            format!("Failed to migrate {} from version {}: {}", schema.file, step, e)
+        })?;
+    }
+    Ok((value, original))
+}
+
+/// Decodes a file of any known version into its records.
+pub fn decode<T: DeserializeOwned>(schema: &Schema, bytes: &[u8]) -> Result<Vec<T>, String> {
+    let value: Value = serde_json::from_slice(bytes)
+        .map_err(|e| format!("Failed to parse {}: {}", schema.file, e))?;
+    let (value, _) = migrate(schema, value)?;
+
+    let envelope: OwnedEnvelope<T> = serde_json::from_value(value)
+        .map_err(|e| format!("Failed to deserialize {}: {}", schema.file, e))?;
+    Ok(envelope.items)
+}
+
+/// Encodes records in the current version.
+pub fn encode<T: Serialize>(schema: &Schema, items: &[T]) -> Result<Vec<u8>, String> {
+    serde_json::to_vec(&Envelope { version: schema.version, items })
+        .map_err(|e| format!("Failed to serialize {}: {}", schema.file, e))
+}
+
+pub fn load<T: DeserializeOwned + Serialize>(schema: &Schema) -> Result<Vec<T>, String> {
+    let bytes = match storage::read(schema.file)
+        .map_err(|e| format!("Failed to read {}: {}", schema.file, e))?
+    {
+        Some(bytes) => bytes,
+        None => return Ok(Vec::new()),
+    };
+
+    let value: Value = serde_json::from_slice(&bytes)
+        .map_err(|e| format!("Failed to parse {}: {}", schema.file, e))?;
+    let (value, original) = migrate(schema, value)?;
+    let envelope: OwnedEnvelope<T> = serde_json::from_value(value)
+        .map_err(|e| format!("Failed to deserialize {}: {}", schema.file, e))?;
+
+    if original < schema.version {
+        let backup_name = format!("{}.v{}.bak", schema.file, original);
+        storage::write(&backup_name, &bytes)
+            .map_err(|e| format!("Failed to back up {} before migration: {}", schema.file, e))?;
+        save(schema, &envelope.items)?;
+    }
+
+    Ok(envelope.items)
+}
+
+pub fn save<T: Serialize>(schema: &Schema, items: &[T]) -> Result<(), String> {
+    storage::write(schema.file, &encode(schema, items)?)
+        .map_err(|e| format!("Failed to write {}: {}", schema.file, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Task};
+
+    #[test]
+    fn migrates_unversioned_tasks() {
+        let legacy = br#"[
+            {"id":"1","title":"Write report","date":"2024-05-01","description":"","project":"Work","completed":true,"completed_on":"2024-05-02T10:00:00.000Z","updated_at":"2024-05-02T10:00:00.000Z","pending_sync":false},
+            {"id":"2","title":"Old task without optional fields"}
+        ]"#;
+
+        let tasks: Vec<Task> = decode(&TASKS, legacy).unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].project, "Work");
+        assert!(tasks[0].completed);
+        assert_eq!(tasks[1].title, "Old task without optional fields");
+        assert!(!tasks[1].completed);
+    }
+
+    #[test]
+    fn migrates_unversioned_events() {
+        let legacy = br#"[{"id":"e1","title":"Concert","description":null,"date_start":"2024-06-01","date_end":null,"time_start":"19:00","time_end":"22:00","location":"Jakarta","latitude":-6.2,"longitude":106.8,"updated_at":null}]"#;
+
+        let events: Vec<Event> = decode(&EVENTS, legacy).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].location.as_deref(), Some("Jakarta"));
+        assert_eq!(events[0].latitude, Some(-6.2));
+    }
+
+    #[test]
+    fn adds_project_ids_to_v1_tasks() {
+        let v1 = json!({ "version": 1, "items": [{ "id": "1", "title": "Task", "project": "Home" }] });
+
+        let (value, original) = migrate(&TASKS, v1).unwrap();
+        assert_eq!(original, 1);
+        assert_eq!(value["version"], json!(2));
+        assert_eq!(value["items"][0]["project_id"], Value::Null);
+        assert_eq!(value["items"][0]["project"], json!("Home"));
+    }
+
+    #[test]
+    fn adds_timezones_to_v1_events() {
+        let v1 = json!({ "version": 1, "items": [
+            { "id": "e1", "title": "Standup", "time_start": "09:00" },
+            { "id": "e2", "title": "Call", "timezone": "Asia/Tokyo" }
+        ] });
+
+        let (value, original) = migrate(&EVENTS, v1).unwrap();
+        assert_eq!(original, 1);
+        assert_eq!(value["version"], json!(2));
+        let zone = crate::timezones::system_zone().map(|zone| json!(zone.name()));
+        assert_eq!(value["items"][0]["timezone"], zone.unwrap_or(Value::Null));
+        assert_eq!(value["items"][1]["timezone"], json!("Asia/Tokyo"));
+    }
+
+    #[test]
+    fn reports_original_version() {
+        let (value, original) = migrate(&TASKS, json!([])).unwrap();
+        assert_eq!(original, 0);
+        assert_eq!(value["version"], json!(TASKS.version));
+
+        let (_, original) = migrate(&TASKS, json!({ "version": TASKS.version, "items": [] })).unwrap();
+        assert_eq!(original, TASKS.version);
+    }
+
+    #[test]
+    fn round_trips_current_version() {
+        let tasks = vec![Task {
+            id: "1".to_string(),
+            title: "Round trip".to_string(),
+            ..Default::default()
+        }];
+
+        let bytes = encode(&TASKS, &tasks).unwrap();
+        let decoded: Vec<Task> = decode(&TASKS, &bytes).unwrap();
+        assert_eq!(decoded[0].title, "Round trip");
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let newer = json!({ "version": TASKS.version + 1, "items": [] });
+        assert!(migrate(&TASKS, newer).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_formats() {
+        assert!(decode::<Task>(&TASKS, b"\"not a list\"").is_err());
+        assert!(decode::<Task>(&TASKS, br#"{"items": []}"#).is_err());
+    }
+}
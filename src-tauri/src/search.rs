@@ -0,0 +1,405 @@
+// Search
+//
+// One inverted index over local and archived tasks, local events, the Asana
+// task cache and the cached Google events. Before every search each source's
+// file is fingerprinted; only sources that changed are re-read, and within
+// them only documents whose content changed are re-indexed.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tauri::command;
+
+use crate::archive::ArchivedTask;
+use crate::{profiles, schema, storage, Event, Task};
+
+const DEFAULT_LIMIT: usize = 20;
+const SNIPPET_RADIUS: usize = 60;
+const TITLE_WEIGHT: f32 = 3.0;
+const PREFIX_WEIGHT: f32 = 0.5;
+
+static INDEX: Mutex<Option<SearchIndex>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    LocalTask,
+    ArchivedTask,
+    LocalEvent,
+    AsanaTask,
+    GoogleEvent,
+}
+
+const SOURCES: [Source; 5] = [
+    Source::LocalTask,
+    Source::ArchivedTask,
+    Source::LocalEvent,
+    Source::AsanaTask,
+    Source::GoogleEvent,
+];
+
+impl Source {
+    fn file(self) -> &'static str {
+        match self {
+            Source::LocalTask => schema::TASKS.file,
+            Source::ArchivedTask => schema::ARCHIVED_TASKS.file,
+            Source::LocalEvent => schema::EVENTS.file,
+            Source::AsanaTask => "asana_tasks_cache.json",
+            Source::GoogleEvent => "google_events_cache.json",
+        }
+    }
+}
+
+type DocKey = (Source, String);
+
+struct Document {
+    title: String,
+    body: String,
+    date: Option<String>,
+    hash: u64,
+    terms: Vec<String>,
+}
+
+#[derive(Default)]
+struct SearchIndex {
+    profile: String,
+    fingerprints: HashMap<Source, String>,
+    docs: HashMap<DocKey, Document>,
+    // term -> document -> weighted term frequency
+    postings: BTreeMap<String, HashMap<DocKey, f32>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct SearchOptions {
+    // Empty means every source
+    sources: Vec<Source>,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct SearchResult {
+    source: Source,
+    id: String,
+    title: String,
+    date: Option<String>,
+    snippet: String,
+    // [start, end) character offsets of matches within the snippet
+    highlights: Vec<(usize, usize)>,
+    score: f32,
+}
+
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| t.chars().count() > 1)
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+fn content_hash(title: &str, body: &str, date: &Option<String>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (title, body, date).hash(&mut hasher);
+    hasher.finish()
+}
+
+fn join_text(parts: &[Option<&str>]) -> String {
+    parts
+        .iter()
+        .flatten()
+        .filter(|p| !p.trim().is_empty())
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" \u{b7} ")
+}
+
+// (id, title, body, date)
+type Extracted = (String, String, String, Option<String>);
+
+fn task_document(t: Task) -> Extracted {
+    let tags = t.tags.join(" ");
+    let body = join_text(&[Some(&t.description), Some(&t.project), Some(&tags)]);
+    let date = Some(t.date).filter(|d| !d.is_empty());
+    (t.id, t.title, body, date)
+}
+
+fn extract(source: Source, bytes: &[u8]) -> Result<Vec<Extracted>, String> {
+    let docs = match source {
+        Source::LocalTask => schema::decode::<Task>(&schema::TASKS, bytes)?
+            .into_iter()
+            .map(task_document)
+            .collect(),
+        Source::ArchivedTask => schema::decode::<ArchivedTask>(&schema::ARCHIVED_TASKS, bytes)?
+            .into_iter()
+            .map(|a| task_document(a.task))
+            .collect(),
+        Source::LocalEvent => schema::decode::<Event>(&schema::EVENTS, bytes)?
+            .into_iter()
+            .map(|e| {
+                let body = join_text(&[e.description.as_deref(), e.location.as_deref()]);
+                (e.id, e.title, body, e.date_start)
+            })
+            .collect(),
+        Source::AsanaTask => {
+            let value: Value = serde_json::from_slice(bytes)
+                .map_err(|e| format!("Failed to parse Asana cache: {}", e))?;
+            value
+                .as_array()
+                .map(|tasks| {
+                    tasks
+                        .iter()
+                        .filter_map(|t| {
+                            let title = t["name"].as_str()?.to_string();
+                            let id = t["gid"].as_str().map(String::from).unwrap_or_else(|| title.clone());
+                            let body = join_text(&[t["notes"].as_str()]);
+                            Some((id, title, body, t["due_on"].as_str().map(String::from)))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+        Source::GoogleEvent => {
+            let events: Vec<crate::CalendarEvent> = serde_json::from_slice(bytes)
+                .map_err(|e| format!("Failed to parse Google events cache: {}", e))?;
+            events
+                .into_iter()
+                .map(|e| {
+                    // Google events are cached without their id
+                    let id = format!("{}|{}", e.summary, e.start.clone().unwrap_or_default());
+                    let body = join_text(&[e.description.as_deref(), e.location.as_deref()]);
+                    (id, e.summary, body, e.start)
+                })
+                .collect()
+        }
+    };
+    Ok(docs)
+}
+
+impl SearchIndex {
+    fn remove(&mut self, key: &DocKey) {
+        if let Some(doc) = self.docs.remove(key) {
+            for term in doc.terms {
+                if let Some(postings) = self.postings.get_mut(&term) {
+                    postings.remove(key);
+                    if postings.is_empty() {
+                        self.postings.remove(&term);
+                    }
+                }
+            }
+        }
+    }
+
+    fn add(&mut self, key: DocKey, title: String, body: String, date: Option<String>, hash: u64) {
+        let mut weights: HashMap<String, f32> = HashMap::new();
+        for term in tokenize(&title) {
+            *weights.entry(term).or_default() += TITLE_WEIGHT;
+        }
+        for term in tokenize(&body) {
+            *weights.entry(term).or_default() += 1.0;
+        }
+
+        for (term, weight) in &weights {
+            self.postings
+                .entry(term.clone())
+                .or_default()
+                .insert(key.clone(), *weight);
+        }
+
+        self.docs.insert(key, Document {
+            title,
+            body,
+            date,
+            hash,
+            terms: weights.into_keys().collect(),
+        });
+    }
+
+    fn sync_source(&mut self, source: Source) -> Result<(), String> {
+        let bytes = storage::read(source.file())?.unwrap_or_default();
+        let fingerprint = format!("{:x}", Sha256::digest(&bytes));
+        if self.fingerprints.get(&source) == Some(&fingerprint) {
+            return Ok(());
+        }
+
+        let docs = if bytes.is_empty() { Vec::new() } else { extract(source, &bytes)? };
+        let current: HashSet<DocKey> = docs.iter().map(|(id, ..)| (source, id.clone())).collect();
+
+        let stale: Vec<DocKey> = self
+            .docs
+            .keys()
+            .filter(|key| key.0 == source && !current.contains(*key))
+            .cloned()
+            .collect();
+        for key in stale {
+            self.remove(&key);
+        }
+
+        for (id, title, body, date) in docs {
+            let key = (source, id);
+            let hash = content_hash(&title, &body, &date);
+            if self.docs.get(&key).map(|d| d.hash) == Some(hash) {
+                continue;
+            }
+            self.remove(&key);
+            self.add(key, title, body, date, hash);
+        }
+
+        self.fingerprints.insert(source, fingerprint);
+        Ok(())
+    }
+
+    // Documents matching every query term, with their scores
+    fn score(&self, terms: &[String], sources: &[Source]) -> Vec<(DocKey, f32)> {
+        let total_docs = self.docs.len().max(1) as f32;
+        let mut scores: HashMap<&DocKey, (f32, usize)> = HashMap::new();
+
+        for (i, term) in terms.iter().enumerate() {
+            let mut best: HashMap<&DocKey, f32> = HashMap::new();
+            for (indexed, postings) in self.postings.range(term.clone()..) {
+                if !indexed.starts_with(term.as_str()) {
+                    break;
+                }
+                let exactness = if indexed == term { 1.0 } else { PREFIX_WEIGHT };
+                let idf = (1.0 + total_docs / postings.len() as f32).ln();
+                for (key, weight) in postings {
+                    let score = weight * idf * exactness;
+                    let entry = best.entry(key).or_default();
+                    *entry = entry.max(score);
+                }
+            }
+
+            for (key, score) in best {
+                let entry = scores.entry(key).or_default();
+                if entry.1 == i {
+                    entry.0 += score;
+                    entry.1 += 1;
+                }
+            }
+        }
+
+        scores
+            .into_iter()
+            .filter(|(key, (_, matched))| {
+                *matched == terms.len() && (sources.is_empty() || sources.contains(&key.0))
+            })
+            .map(|(key, (score, _))| (key.clone(), score))
+            .collect()
+    }
+}
+
+// Cuts a window around the first match and marks every match inside it, or
+// None when the text has no match.
+fn snippet(text: &str, terms: &[String]) -> Option<(String, Vec<(usize, usize)>)> {
+    let chars: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = chars
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+    let needles: Vec<Vec<char>> = terms.iter().map(|t| t.chars().collect()).collect();
+
+    let find_at = |pos: usize| {
+        needles
+            .iter()
+            .filter(|n| !n.is_empty() && lower[pos..].starts_with(n))
+            .map(|n| n.len())
+            .max()
+    };
+
+    let first = (0..lower.len()).find(|&pos| find_at(pos).is_some())?;
+    let start = first.saturating_sub(SNIPPET_RADIUS);
+    let end = (first + SNIPPET_RADIUS * 2).min(chars.len());
+
+    let mut highlights = Vec::new();
+    let mut pos = start;
+    while pos < end {
+        match find_at(pos) {
+            Some(len) => {
+                let match_end = (pos + len).min(end);
+                highlights.push((pos - start, match_end - start));
+                pos = match_end;
+            }
+            None => pos += 1,
+        }
+    }
+
+    let mut snippet: String = chars[start..end].iter().collect();
+    if start > 0 {
+        snippet.insert(0, '\u{2026}');
+        for h in highlights.iter_mut() {
+            h.0 += 1;
+            h.1 += 1;
+        }
+    }
+    if end < chars.len() {
+        snippet.push('\u{2026}');
+    }
+    Some((snippet, highlights))
+}
+
+#[command]
+pub fn search_all(query: String, options: Option<SearchOptions>) -> Result<Vec<SearchResult>, String> {
+    let options = options.unwrap_or_default();
+    let terms = tokenize(&query);
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut guard = INDEX.lock().map_err(|_| "Search index lock poisoned".to_string())?;
+    let profile = profiles::active_profile();
+    if guard.as_ref().is_none_or(|index| index.profile != profile) {
+        *guard = Some(SearchIndex { profile, ..Default::default() });
+    }
+    let index = guard.as_mut().ok_or_else(|| "Search index unavailable".to_string())?;
+
+    for source in SOURCES {
+        if options.sources.is_empty() || options.sources.contains(&source) {
+            index.sync_source(source)?;
+        }
+    }
+
+    let mut scored = index.score(&terms, &options.sources);
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.truncate(options.limit.unwrap_or(DEFAULT_LIMIT));
+
+    let results = scored
+        .into_iter()
+        .filter_map(|(key, score)| {
+            let doc = index.docs.get(&key)?;
+            // Matches only in the title show the title
+            let (snippet, highlights) = snippet(&doc.body, &terms)
+                .or_else(|| snippet(&doc.title, &terms))
+                .unwrap_or_else(|| (doc.title.clone(), Vec::new()));
+            Some(SearchResult {
+                source: key.0,
+                id: key.1,
+                title: doc.title.clone(),
+                date: doc.date.clone(),
+                snippet,
+                highlights,
+                score,
+            })
+        })
+        .collect();
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlights_matches_in_the_snippet() {
+        let (text, highlights) = snippet("Buy milk and more Milk", &["milk".to_string()]).unwrap();
+        assert_eq!(text, "Buy milk and more Milk");
+        assert_eq!(highlights, vec![(4, 8), (18, 22)]);
+    }
+
+    #[test]
+    fn no_snippet_without_a_match() {
+        assert!(snippet("Nothing relevant here", &["milk".to_string()]).is_none());
+    }
+}
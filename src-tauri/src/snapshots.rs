@@ -0,0 +1,237 @@
+// Scheduled snapshots
+//
+// A background job archives the active profile's local tasks and events on a
+// fixed interval and prunes old snapshots with a daily/weekly retention rule.
+// Snapshots use the same archive format as manual backups, encrypted like the
+// data files when at-rest encryption is enabled.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use chrono::{Datelike, Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+use crate::backup::{self, RestoreMode, RestorePreview};
+use crate::{profiles, storage};
+
+const SETTINGS_FILE: &str = "snapshot_settings.json";
+const SNAPSHOT_FILES: &[&str] = &["local_tasks_cache.json", "local_events_cache.json"];
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+const CHECK_INTERVAL_SECS: u64 = 60;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SnapshotSettings {
+    enabled: bool,
+    interval_hours: u32,
+    // Defaults to a "daspberry_snapshots" folder inside the profile directory
+    folder: Option<String>,
+    keep_daily: usize,
+    keep_weekly: usize,
+}
+
+impl Default for SnapshotSettings {
+    fn default() -> Self {
+        SnapshotSettings {
+            enabled: false,
+            interval_hours: 24,
+            folder: None,
+            keep_daily: 7,
+            keep_weekly: 4,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Snapshot {
+    name: String,
+    path: String,
+    created_at: String,
+    size: u64,
+    #[serde(skip)]
+    taken_at: NaiveDateTime,
+}
+
+fn load_settings() -> Result<SnapshotSettings, String> {
+    let json = match storage::read_to_string(SETTINGS_FILE)
+        .map_err(|e| format!("Failed to read snapshot settings: {}", e))?
+    {
+        Some(json) => json,
+        None => return Ok(SnapshotSettings::default()),
+    };
+
+    serde_json::from_str(&json).map_err(|e| format!("Failed to deserialize snapshot settings: {}", e))
+}
+
+fn snapshot_dir(settings: &SnapshotSettings) -> Result<PathBuf, String> {
+    let dir = match &settings.folder {
+        Some(folder) if !folder.trim().is_empty() => PathBuf::from(folder),
+        _ => profiles::data_dir()?.join("daspberry_snapshots"),
+    };
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create snapshot folder: {}", e))?;
+    Ok(dir)
+}
+
+// Snapshot names carry the profile so several profiles can share one folder.
+fn name_prefix() -> String {
+    format!("snapshot-{}-", profiles::active_profile())
+}
+
+/// Snapshots of the active profile, newest first.
+fn list_snapshot_files(settings: &SnapshotSettings) -> Result<Vec<Snapshot>, String> {
+    let prefix = name_prefix();
+    let mut snapshots = Vec::new();
+
+    for entry in fs::read_dir(snapshot_dir(settings)?).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        let taken_at = match name
+            .strip_prefix(&prefix)
+            .and_then(|rest| rest.strip_suffix(".zip"))
+            .and_then(|stamp| NaiveDateTime::parse_from_str(stamp, TIMESTAMP_FORMAT).ok())
+        {
+            Some(taken_at) => taken_at,
+            None => continue,
+        };
+
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        snapshots.push(Snapshot {
+            name,
+            path: entry.path().to_string_lossy().into_owned(),
+            created_at: taken_at.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            size,
+            taken_at,
+        });
+    }
+
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.taken_at));
+    Ok(snapshots)
+}
+
+// Keeps the newest snapshot of each of the last `keep_daily` days and of each
+// of the last `keep_weekly` ISO weeks. The newest snapshot is never pruned.
+fn snapshots_to_prune(snapshots: &[Snapshot], keep_daily: usize, keep_weekly: usize) -> Vec<&Snapshot> {
+    let mut days = HashSet::new();
+    let mut weeks = HashSet::new();
+
+    snapshots
+        .iter()
+        .enumerate()
+        .filter(|(i, snapshot)| {
+            let day = snapshot.taken_at.date();
+            let week = day.iso_week();
+            let keep_day = days.len() < keep_daily && days.insert(day);
+            let keep_week = weeks.len() < keep_weekly && weeks.insert((week.year(), week.week()));
+            !(*i == 0 || keep_day || keep_week)
+        })
+        .map(|(_, snapshot)| snapshot)
+        .collect()
+}
+
+fn take_snapshot(settings: &SnapshotSettings) -> Result<Snapshot, String> {
+    let taken_at = Local::now().naive_local();
+    let name = format!("{}{}.zip", name_prefix(), taken_at.format(TIMESTAMP_FORMAT));
+    let path = snapshot_dir(settings)?.join(&name);
+
+    let (_, bytes) = backup::archive_bytes(SNAPSHOT_FILES)?;
+    fs::write(&path, storage::seal(&bytes)?).map_err(|e| format!("Failed to write snapshot: {}", e))?;
+
+    let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    Ok(Snapshot {
+        name,
+        path: path.to_string_lossy().into_owned(),
+        created_at: taken_at.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        size,
+        taken_at,
+    })
+}
+
+/// Snapshot archives of the active profile, for re-encoding when encryption
+/// is turned on or off.
+pub fn snapshot_paths() -> Result<Vec<PathBuf>, String> {
+    Ok(list_snapshot_files(&load_settings()?)?
+        .into_iter()
+        .map(|s| PathBuf::from(s.path))
+        .collect())
+}
+
+fn prune_snapshots(settings: &SnapshotSettings) -> Result<(), String> {
+    let snapshots = list_snapshot_files(settings)?;
+    for snapshot in snapshots_to_prune(&snapshots, settings.keep_daily, settings.keep_weekly) {
+        fs::remove_file(&snapshot.path)
+            .map_err(|e| format!("Failed to remove snapshot {}: {}", snapshot.name, e))?;
+    }
+    Ok(())
+}
+
+fn run_due_snapshot() -> Result<(), String> {
+    let settings = load_settings()?;
+    if !settings.enabled {
+        return Ok(());
+    }
+
+    let interval = chrono::Duration::hours(settings.interval_hours.max(1) as i64);
+    let due = match list_snapshot_files(&settings)?.first() {
+        Some(latest) => Local::now().naive_local() - latest.taken_at >= interval,
+        None => true,
+    };
+
+    if due {
+        take_snapshot(&settings)?;
+        prune_snapshots(&settings)?;
+    }
+    Ok(())
+}
+
+/// Starts the background job. Settings are re-read on every check, so changes
+/// and profile switches take effect without a restart.
+pub fn start_scheduler() {
+    tauri::async_runtime::spawn(async {
+        loop {
+            if let Err(e) = run_due_snapshot() {
+                eprintln!("Scheduled snapshot failed: {}", e);
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+        }
+    });
+}
+
+#[command]
+pub fn get_snapshot_settings() -> Result<SnapshotSettings, String> {
+    load_settings()
+}
+
+#[command]
+pub fn save_snapshot_settings(settings: SnapshotSettings) -> Result<(), String> {
+    let json = serde_json::to_string(&settings)
+        .map_err(|e| format!("Failed to serialize snapshot settings: {}", e))?;
+
+    storage::write(SETTINGS_FILE, json.as_bytes())
+        .map_err(|e| format!("Failed to write snapshot settings: {}", e))
+}
+
+#[command]
+pub fn list_snapshots() -> Result<Vec<Snapshot>, String> {
+    list_snapshot_files(&load_settings()?)
+}
+
+#[command]
+pub fn create_snapshot() -> Result<Snapshot, String> {
+    let settings = load_settings()?;
+    let snapshot = take_snapshot(&settings)?;
+    prune_snapshots(&settings)?;
+    Ok(snapshot)
+}
+
+#[command]
+pub fn restore_snapshot(name: String) -> Result<RestorePreview, String> {
+    let snapshot = list_snapshot_files(&load_settings()?)?
+        .into_iter()
+        .find(|s| s.name == name)
+        .ok_or_else(|| format!("Snapshot '{}' does not exist", name))?;
+
+    let bytes = fs::read(&snapshot.path).map_err(|e| format!("Failed to read snapshot: {}", e))?;
+    backup::restore_archive(storage::open(&snapshot.name, bytes)?, RestoreMode::Replace, SNAPSHOT_FILES)
+}
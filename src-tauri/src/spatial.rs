@@ -0,0 +1,276 @@
+// Spatial queries over local events
+//
+// Events with coordinates are bucketed into a grid of CELL_DEGREES cells, so
+// a query only measures the events in the cells its area touches. The index
+// is rebuilt when the events file changes on disk, which also covers profile
+// switches and restores.
+
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+use crate::geocoding::{distance_meters, valid_coordinates};
+use crate::tasks::{parse_bound, parse_task_date};
+use crate::{profiles, schema, Event};
+
+const CELL_DEGREES: f64 = 0.1;
+const LAT_CELLS: i64 = (180.0 / CELL_DEGREES) as i64;
+const LON_CELLS: i64 = (360.0 / CELL_DEGREES) as i64;
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+static INDEX: Mutex<Option<SpatialIndex>> = Mutex::new(None);
+
+type Cell = (i64, i64);
+// What the index was built from: path, modification time and size
+type Source = (String, Option<SystemTime>, u64);
+
+struct SpatialIndex {
+    source: Source,
+    events: Vec<Event>,
+    cells: HashMap<Cell, Vec<usize>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct DateRange {
+    // Inclusive, YYYY-MM-DD; events overlapping the range match
+    from: Option<String>,
+    to: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct EventDistance {
+    event: Event,
+    distance_km: f64,
+}
+
+fn cell(latitude: f64, longitude: f64) -> Cell {
+    let row = ((latitude + 90.0) / CELL_DEGREES).floor() as i64;
+    let column = ((longitude + 180.0) / CELL_DEGREES).floor() as i64;
+    (row.clamp(0, LAT_CELLS - 1), column.rem_euclid(LON_CELLS))
+}
+
+fn coordinates(event: &Event) -> Option<(f64, f64)> {
+    match (event.latitude, event.longitude) {
+        (Some(lat), Some(lon)) if valid_coordinates(lat, lon) => Some((lat, lon)),
+        _ => None,
+    }
+}
+
+fn current_source() -> Result<Source, String> {
+    let path = profiles::data_file(schema::EVENTS.file)?;
+    let metadata = fs::metadata(&path).ok();
+    Ok((
+        path.to_string_lossy().to_string(),
+        metadata.as_ref().and_then(|m| m.modified().ok()),
+        metadata.map_or(0, |m| m.len()),
+    ))
+}
+
+impl SpatialIndex {
+    fn build(source: Source, events: Vec<Event>) -> Self {
+        let mut cells: HashMap<Cell, Vec<usize>> = HashMap::new();
+        for (i, event) in events.iter().enumerate() {
+            if let Some((lat, lon)) = coordinates(event) {
+                cells.entry(cell(lat, lon)).or_default().push(i);
+            }
+        }
+        SpatialIndex { source, events, cells }
+    }
+
+    // Events in the cells covering latitudes south..=north and the columns
+    // from west eastwards to east, wrapping at the antimeridian.
+    fn candidates(&self, south: f64, north: f64, west: f64, east: f64) -> Vec<&Event> {
+        let (first_row, _) = cell(south.max(-90.0), 0.0);
+        let (last_row, _) = cell(north.min(90.0), 0.0);
+        let (_, first_column) = cell(0.0, west);
+        let (_, last_column) = cell(0.0, east);
+        let mut span = (last_column - first_column).rem_euclid(LON_CELLS) + 1;
+        if east - west >= 360.0 {
+            span = LON_CELLS;
+        }
+
+        // A sparse grid is cheaper to scan than to probe cell by cell
+        let probes = (last_row - first_row + 1) * span;
+        if probes as usize > self.cells.len() {
+            return self
+                .cells
+                .iter()
+                .filter(|((row, column), _)| {
+                    (first_row..=last_row).contains(row) && (column - first_column).rem_euclid(LON_CELLS) < span
+                })
+                .flat_map(|(_, indexes)| indexes.iter().map(|&i| &self.events[i]))
+                .collect();
+        }
+
+        let mut found = Vec::new();
+        for row in first_row..=last_row {
+            for offset in 0..span {
+                let column = (first_column + offset).rem_euclid(LON_CELLS);
+                if let Some(indexes) = self.cells.get(&(row, column)) {
+                    found.extend(indexes.iter().map(|&i| &self.events[i]));
+                }
+            }
+        }
+        found
+    }
+}
+
+// Runs `query` against the index, rebuilding it first if the events changed.
+fn with_index<R>(query: impl FnOnce(&SpatialIndex) -> R) -> Result<R, String> {
+    let source = current_source()?;
+    let mut cached = INDEX.lock().map_err(|_| "Spatial index lock poisoned".to_string())?;
+    let index = match &mut *cached {
+        Some(index) if index.source == source => index,
+        stale => {
+            let events: Vec<Event> = schema::load(&schema::EVENTS)?;
+            stale.insert(SpatialIndex::build(source, events))
+        }
+    };
+    Ok(query(index))
+}
+
+fn in_range(event: &Event, from: Option<NaiveDate>, to: Option<NaiveDate>) -> bool {
+    if from.is_none() && to.is_none() {
+        return true;
+    }
+    let Some(start) = event.date_start.as_deref().and_then(parse_task_date) else {
+        return false;
+    };
+    let end = event.date_end.as_deref().and_then(parse_task_date).unwrap_or(start).max(start);
+    from.is_none_or(|from| end >= from) && to.is_none_or(|to| start <= to)
+}
+
+fn by_distance(events: Vec<&Event>, latitude: f64, longitude: f64, max_km: Option<f64>) -> Vec<EventDistance> {
+    let mut found: Vec<EventDistance> = events
+        .into_iter()
+        .filter_map(|event| {
+            let (lat, lon) = coordinates(event)?;
+            let distance_km = distance_meters(latitude, longitude, lat, lon) / 1000.0;
+            max_km.is_none_or(|max| distance_km <= max).then(|| EventDistance {
+                event: event.clone(),
+                distance_km,
+            })
+        })
+        .collect();
+    found.sort_by(|a, b| a.distance_km.total_cmp(&b.distance_km));
+    found
+}
+
+/// Events within `radius_km` of a point, nearest first.
+#[command]
+pub fn query_events_near(
+    latitude: f64,
+    longitude: f64,
+    radius_km: f64,
+    dates: Option<DateRange>,
+) -> Result<Vec<EventDistance>, String> {
+    if !valid_coordinates(latitude, longitude) {
+        return Err(format!("{}, {} are not valid coordinates", latitude, longitude));
+    }
+    if radius_km.is_nan() || radius_km < 0.0 {
+        return Err("The radius must not be negative".to_string());
+    }
+    let dates = dates.unwrap_or_default();
+    let (from, to) = (parse_bound(&dates.from)?, parse_bound(&dates.to)?);
+
+    let lat_degrees = radius_km * 1000.0 / METERS_PER_DEGREE;
+    let (south, north) = (latitude - lat_degrees, latitude + lat_degrees);
+    // Longitude degrees shrink towards the poles; near them, search all of them
+    let lon_degrees = if south <= -89.0 || north >= 89.0 {
+        360.0
+    } else {
+        let widest = latitude.abs() + lat_degrees;
+        lat_degrees / widest.to_radians().cos()
+    }
+    .min(180.0);
+
+    with_index(|index| {
+        let candidates = index
+            .candidates(south, north, longitude - lon_degrees, longitude + lon_degrees)
+            .into_iter()
+            .filter(|e| in_range(e, from, to))
+            .collect();
+        by_distance(candidates, latitude, longitude, Some(radius_km))
+    })
+}
+
+/// Events inside a map viewport, nearest to its center first. `west` may be
+/// greater than `east` when the viewport crosses the antimeridian.
+#[command]
+pub fn query_events_in_bbox(
+    south: f64,
+    west: f64,
+    north: f64,
+    east: f64,
+    dates: Option<DateRange>,
+) -> Result<Vec<EventDistance>, String> {
+    if !valid_coordinates(south, west) || !valid_coordinates(north, east) || south > north {
+        return Err("Invalid bounding box".to_string());
+    }
+    let dates = dates.unwrap_or_default();
+    let (from, to) = (parse_bound(&dates.from)?, parse_bound(&dates.to)?);
+
+    let east_unwrapped = if east < west { east + 360.0 } else { east };
+    let center_lat = (south + north) / 2.0;
+    let center_lon = ((west + east_unwrapped) / 2.0 + 180.0).rem_euclid(360.0) - 180.0;
+    let inside_lon = |lon: f64| {
+        if west <= east {
+            (west..=east).contains(&lon)
+        } else {
+            lon >= west || lon <= east
+        }
+    };
+
+    with_index(|index| {
+        let candidates = index
+            .candidates(south, north, west, east_unwrapped)
+            .into_iter()
+            .filter(|e| {
+                coordinates(e).is_some_and(|(lat, lon)| (south..=north).contains(&lat) && inside_lon(lon))
+            })
+            .filter(|e| in_range(e, from, to))
+            .collect();
+        by_distance(candidates, center_lat, center_lon, None)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn event(id: &str, latitude: f64, longitude: f64) -> Event {
+        serde_json::from_value(json!({ "id": id, "title": id, "latitude": latitude, "longitude": longitude })).unwrap()
+    }
+
+    fn ids(events: Vec<&Event>) -> Vec<&str> {
+        let mut ids: Vec<&str> = events.iter().map(|e| e.id.as_str()).collect();
+        ids.sort();
+        ids
+    }
+
+    fn index() -> SpatialIndex {
+        let events = vec![event("east", 0.01, 179.95), event("west", 0.01, -179.95), event("greenwich", 0.01, 0.0)];
+        SpatialIndex::build((String::new(), None, 0), events)
+    }
+
+    #[test]
+    fn wraps_scanned_boxes_at_the_antimeridian() {
+        assert_eq!(ids(index().candidates(-1.0, 1.0, 179.9, -179.9)), vec!["east", "west"]);
+    }
+
+    #[test]
+    fn wraps_probed_boxes_at_the_antimeridian() {
+        assert_eq!(ids(index().candidates(0.0, 0.05, 179.95, -179.95)), vec!["east", "west"]);
+    }
+
+    #[test]
+    fn covers_the_globe_for_full_width_boxes() {
+        assert_eq!(ids(index().candidates(-90.0, 90.0, -180.0, 180.0)), vec!["east", "greenwich", "west"]);
+    }
+}
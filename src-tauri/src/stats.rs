@@ -0,0 +1,269 @@
+// Task statistics
+//
+// Aggregates the local task store and the task archive for the progress
+// screen, so history and streaks survive archiving. Completion days are
+// counted in the user's zone, weeks start on Monday.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use chrono::{DateTime, Datelike, Duration, NaiveDate};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+use crate::tasks::{is_overdue, parse_task_date};
+use crate::{archive, schema, timezones, Task};
+
+const DEFAULT_DAYS: u32 = 30;
+const DEFAULT_WEEKS: u32 = 12;
+// About ten years of series at most
+const MAX_DAYS: u32 = 3660;
+const MAX_WEEKS: u32 = 520;
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct StatsOptions {
+    // Length of the per-day and per-week series, ending today
+    days: Option<u32>,
+    weeks: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct DayCount {
+    date: String,
+    completed: usize,
+}
+
+#[derive(Serialize)]
+pub struct WeekCount {
+    // Monday of the week
+    week_start: String,
+    completed: usize,
+}
+
+#[derive(Serialize)]
+pub struct ProjectStats {
+    project: String,
+    project_id: Option<String>,
+    completed: usize,
+    open: usize,
+    overdue: usize,
+}
+
+#[derive(Serialize)]
+pub struct TaskStats {
+    total: usize,
+    completed: usize,
+    open: usize,
+    overdue: usize,
+    completed_today: usize,
+    completed_this_week: usize,
+    per_day: Vec<DayCount>,
+    per_week: Vec<WeekCount>,
+    per_project: Vec<ProjectStats>,
+    // Consecutive days with at least one completion, up to today (or
+    // yesterday, while nothing has been completed yet today)
+    current_streak: u32,
+    longest_streak: u32,
+    // Only tasks that know when they were created count towards this
+    average_completion_hours: Option<f64>,
+}
+
+fn parse_timestamp(value: &str, zone: Tz) -> Option<DateTime<Tz>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|t| t.with_timezone(&zone))
+}
+
+// Day a task was completed on, in the user's zone
+fn completion_day(task: &Task, zone: Tz) -> Option<NaiveDate> {
+    let completed_on = task.completed_on.as_deref().filter(|_| task.completed)?;
+    parse_timestamp(completed_on, zone)
+        .map(|t| t.date_naive())
+        .or_else(|| parse_task_date(completed_on))
+}
+
+pub fn week_start(day: NaiveDate) -> NaiveDate {
+    day - Duration::days(day.weekday().num_days_from_monday() as i64)
+}
+
+fn streaks(days: &BTreeSet<NaiveDate>, today: NaiveDate) -> (u32, u32) {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for &day in days {
+        run = match previous {
+            Some(prev) if day - prev == Duration::days(1) => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(day);
+    }
+
+    let mut current = 0;
+    let mut day = if days.contains(&today) { today } else { today - Duration::days(1) };
+    while days.contains(&day) {
+        current += 1;
+        day -= Duration::days(1);
+    }
+    (current, longest)
+}
+
+fn compute(tasks: &[Task], today: NaiveDate, options: &StatsOptions) -> TaskStats {
+    let zone = timezones::user_zone();
+    let days = options.days.unwrap_or(DEFAULT_DAYS).clamp(1, MAX_DAYS) as i64;
+    let weeks = options.weeks.unwrap_or(DEFAULT_WEEKS).clamp(1, MAX_WEEKS) as i64;
+
+    let mut per_day: HashMap<NaiveDate, usize> = HashMap::new();
+    // Keyed by project id, or by name for tasks not linked to a project yet
+    let mut per_project: BTreeMap<(Option<String>, String), ProjectStats> = BTreeMap::new();
+    let mut completion_hours = Vec::new();
+    let mut overdue = 0;
+
+    for task in tasks {
+        let task_overdue = is_overdue(task, today);
+        if task_overdue {
+            overdue += 1;
+        }
+
+        if let Some(day) = completion_day(task, zone) {
+            *per_day.entry(day).or_default() += 1;
+        }
+
+        if let (Some(created), Some(completed)) = (
+            task.created_at.as_deref().and_then(|t| parse_timestamp(t, zone)),
+            task.completed_on.as_deref().filter(|_| task.completed).and_then(|t| parse_timestamp(t, zone)),
+        ) {
+            if completed >= created {
+                completion_hours.push((completed - created).num_minutes() as f64 / 60.0);
+            }
+        }
+
+        let project = task.project.trim();
+        if !project.is_empty() {
+            let key = match &task.project_id {
+                Some(id) => (Some(id.clone()), String::new()),
+                None => (None, project.to_lowercase()),
+            };
+            let entry = per_project
+                .entry(key)
+                .or_insert_with(|| ProjectStats {
+                    project: project.to_string(),
+                    project_id: task.project_id.clone(),
+                    completed: 0,
+                    open: 0,
+                    overdue: 0,
+                });
+            if task.completed {
+                entry.completed += 1;
+            } else {
+                entry.open += 1;
+            }
+            if task_overdue {
+                entry.overdue += 1;
+            }
+        }
+    }
+
+    let day_series: Vec<DayCount> = (0..days)
+        .rev()
+        .map(|offset| {
+            let day = today - Duration::days(offset);
+            DayCount {
+                date: day.format("%Y-%m-%d").to_string(),
+                completed: per_day.get(&day).copied().unwrap_or(0),
+            }
+        })
+        .collect();
+
+    let mut per_week: HashMap<NaiveDate, usize> = HashMap::new();
+    for (day, count) in &per_day {
+        *per_week.entry(week_start(*day)).or_default() += count;
+    }
+    let this_week = week_start(today);
+    let week_series: Vec<WeekCount> = (0..weeks)
+        .rev()
+        .map(|offset| {
+            let start = this_week - Duration::weeks(offset);
+            WeekCount {
+                week_start: start.format("%Y-%m-%d").to_string(),
+                completed: per_week.get(&start).copied().unwrap_or(0),
+            }
+        })
+        .collect();
+
+    let completed_days: BTreeSet<NaiveDate> = per_day.keys().copied().collect();
+    let (current_streak, longest_streak) = streaks(&completed_days, today);
+
+    let average_completion_hours = if completion_hours.is_empty() {
+        None
+    } else {
+        Some(completion_hours.iter().sum::<f64>() / completion_hours.len() as f64)
+    };
+
+    let completed = tasks.iter().filter(|t| t.completed).count();
+    let mut per_project: Vec<ProjectStats> = per_project.into_values().collect();
+    per_project.sort_by(|a, b| b.completed.cmp(&a.completed).then_with(|| a.project.cmp(&b.project)));
+
+    TaskStats {
+        total: tasks.len(),
+        completed,
+        open: tasks.len() - completed,
+        overdue,
+        completed_today: per_day.get(&today).copied().unwrap_or(0),
+        completed_this_week: per_week.get(&this_week).copied().unwrap_or(0),
+        per_day: day_series,
+        per_week: week_series,
+        per_project,
+        current_streak,
+        longest_streak,
+        average_completion_hours,
+    }
+}
+
+#[command]
+pub fn get_task_stats(options: Option<StatsOptions>) -> Result<TaskStats, String> {
+    let mut tasks: Vec<Task> = schema::load(&schema::TASKS)?;
+    tasks.extend(archive::load_archive()?.into_iter().map(|a| a.task));
+    let today = timezones::today();
+    Ok(compute(&tasks, today, &options.unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completed(id: &str, project: &str, project_id: Option<&str>, completed_on: &str) -> Task {
+        Task {
+            id: id.to_string(),
+            project: project.to_string(),
+            project_id: project_id.map(String::from),
+            completed: true,
+            completed_on: Some(completed_on.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn clamps_series_length() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let options = StatsOptions { days: Some(u32::MAX), weeks: Some(u32::MAX) };
+        let stats = compute(&[], today, &options);
+        assert_eq!(stats.per_day.len(), MAX_DAYS as usize);
+        assert_eq!(stats.per_week.len(), MAX_WEEKS as usize);
+    }
+
+    #[test]
+    fn groups_projects_by_id_and_counts_streaks() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let tasks = vec![
+            completed("1", "Work", Some("p1"), "2024-05-09"),
+            completed("2", "work", Some("p2"), "2024-05-10"),
+            completed("3", "Work", Some("p1"), "2024-05-07"),
+        ];
+        let stats = compute(&tasks, today, &StatsOptions::default());
+        assert_eq!(stats.per_project.len(), 2);
+        assert_eq!(stats.per_project[0].project_id.as_deref(), Some("p1"));
+        assert_eq!(stats.per_project[0].completed, 2);
+        assert_eq!((stats.current_streak, stats.longest_streak), (2, 2));
+    }
+}
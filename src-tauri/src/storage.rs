@@ -0,0 +1,452 @@
+// Data storage
+//
+// Every profile data file is read and written through here so it can be
+// encrypted at rest. Encrypted files start with a magic header, followed by a
+// random nonce and the AES-256-GCM ciphertext. Files without the header are
+// read as plaintext, so data written before encryption was enabled (or while
+// it is being re-encrypted) stays readable. Migration backups and snapshot
+// archives are re-encoded along with the data files. Files are replaced
+// through a temporary file and a rename, so a crash never leaves one torn.
+
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tauri::command;
+
+use crate::{profiles, snapshots};
+
+const MAGIC: &[u8] = b"DASPENC1";
+const NONCE_LEN: usize = 12;
+const CONFIG_FILE: &str = "encryption.json";
+const KEYRING_SERVICE: &str = "Daspberry";
+const KDF_ITERATIONS: u32 = 600_000;
+const KEY_CHECK: &[u8] = b"daspberry";
+
+/// Every data file a profile can hold.
+pub const DATA_FILES: &[&str] = &[
+    "local_tasks_cache.json",
+    "local_events_cache.json",
+    "task_archive.json",
+    "purged_tasks.json",
+    "projects.json",
+    "github_repos_cache.json",
+    "asana_tasks_cache.json",
+    "asana_user_details_cache.json",
+    "google_events_cache.json",
+    "google_tokens_cache.json",
+    "snapshot_settings.json",
+    "focus_settings.json",
+    "focus_sessions.json",
+    "time_entries.json",
+    "archive_settings.json",
+    "undo_journal.json",
+    "notes.json",
+    "geocoding_settings.json",
+    "geocoding_cache.json",
+    "travel_settings.json",
+];
+
+/// Data files that stay out of backups: credentials, the undo history and a
+/// cache that is rebuilt on demand.
+pub const LOCAL_ONLY_FILES: &[&str] = &[
+    "google_tokens_cache.json",
+    "undo_journal.json",
+    "geocoding_cache.json",
+];
+
+/// Directories whose files are all data files, such as note documents.
+pub const DATA_DIRS: &[&str] = &["notes", "attachments"];
+
+type DataKey = [u8; 32];
+
+// Unlocked keys, per profile
+static KEYS: Mutex<Vec<(String, DataKey)>> = Mutex::new(Vec::new());
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeySource {
+    Passphrase,
+    Keyring,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EncryptionConfig {
+    source: KeySource,
+    // Only used for passphrase-derived keys
+    salt: String,
+    iterations: u32,
+    // KEY_CHECK encrypted with the key, used to verify a passphrase
+    key_check: String,
+}
+
+#[derive(Serialize)]
+pub struct EncryptionStatus {
+    enabled: bool,
+    source: Option<KeySource>,
+    unlocked: bool,
+}
+
+fn load_config() -> Result<Option<EncryptionConfig>, String> {
+    let path = profiles::data_file(CONFIG_FILE)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let json = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read encryption config: {}", e))?;
+    serde_json::from_str(&json)
+        .map(Some)
+        .map_err(|e| format!("Failed to deserialize encryption config: {}", e))
+}
+
+fn save_config(config: &EncryptionConfig) -> Result<(), String> {
+    let json = serde_json::to_string(config)
+        .map_err(|e| format!("Failed to serialize encryption config: {}", e))?;
+    fs::write(profiles::data_file(CONFIG_FILE)?, json)
+        .map_err(|e| format!("Failed to write encryption config: {}", e))
+}
+
+fn cached_key() -> Option<DataKey> {
+    let profile = profiles::active_profile();
+    let keys = KEYS.lock().ok()?;
+    keys.iter().find(|(p, _)| *p == profile).map(|(_, key)| *key)
+}
+
+fn cache_key(key: DataKey) {
+    let profile = profiles::active_profile();
+    if let Ok(mut keys) = KEYS.lock() {
+        keys.retain(|(p, _)| *p != profile);
+        keys.push((profile, key));
+    }
+}
+
+fn forget_key() {
+    let profile = profiles::active_profile();
+    if let Ok(mut keys) = KEYS.lock() {
+        keys.retain(|(p, _)| *p != profile);
+    }
+}
+
+fn keyring_entry() -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, &profiles::active_profile())
+        .map_err(|e| format!("Failed to open OS keyring: {}", e))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> DataKey {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, iterations, &mut key);
+    key
+}
+
+fn current_key(config: &EncryptionConfig) -> Result<DataKey, String> {
+    if let Some(key) = cached_key() {
+        return Ok(key);
+    }
+
+    match config.source {
+        KeySource::Keyring => {
+            let encoded = keyring_entry()?
+                .get_password()
+                .map_err(|e| format!("Failed to read key from OS keyring: {}", e))?;
+            let bytes = STANDARD
+                .decode(encoded)
+                .map_err(|e| format!("Invalid key in OS keyring: {}", e))?;
+            let key: DataKey = bytes
+                .try_into()
+                .map_err(|_| "Invalid key length in OS keyring".to_string())?;
+            cache_key(key);
+            Ok(key)
+        }
+        KeySource::Passphrase => Err("Data is encrypted; unlock it with your passphrase first".to_string()),
+    }
+}
+
+fn encrypt(key: &DataKey, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| "Failed to encrypt data".to_string())?;
+
+    let mut out = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+fn decrypt(key: &DataKey, data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < MAGIC.len() + NONCE_LEN {
+        return Err("Encrypted data is truncated".to_string());
+    }
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let (nonce, ciphertext) = data[MAGIC.len()..].split_at(NONCE_LEN);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Failed to decrypt data: wrong key or corrupted file".to_string())
+}
+
+fn decode(name: &str, bytes: Vec<u8>, key: Option<&DataKey>) -> Result<Vec<u8>, String> {
+    if !bytes.starts_with(MAGIC) {
+        return Ok(bytes);
+    }
+    match key {
+        Some(key) => decrypt(key, &bytes).map_err(|e| format!("{}: {}", name, e)),
+        None => Err(format!("{} is encrypted but encryption is not enabled", name)),
+    }
+}
+
+/// Reads a data file of the active profile, decrypting it when needed.
+/// Returns None when the file does not exist.
+pub fn read(name: &str) -> Result<Option<Vec<u8>>, String> {
+    let path = profiles::data_file(name)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let bytes = fs::read(&path).map_err(|e| e.to_string())?;
+    if !bytes.starts_with(MAGIC) {
+        return Ok(Some(bytes));
+    }
+
+    let config = load_config()?;
+    let key = config.as_ref().map(current_key).transpose()?;
+    decode(name, bytes, key.as_ref()).map(Some)
+}
+
+pub fn read_to_string(name: &str) -> Result<Option<String>, String> {
+    match read(name)? {
+        Some(bytes) => String::from_utf8(bytes)
+            .map(Some)
+            .map_err(|e| format!("{} is not valid UTF-8: {}", name, e)),
+        None => Ok(None),
+    }
+}
+
+/// Writes a data file of the active profile, encrypting it when encryption is enabled.
+pub fn write(name: &str, bytes: &[u8]) -> Result<(), String> {
+    let path = profiles::data_file(name)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let bytes = match load_config()? {
+        Some(config) => encrypt(&current_key(&config)?, bytes)?,
+        None => bytes.to_vec(),
+    };
+    replace_file(&path, &bytes).map_err(|e| e.to_string())
+}
+
+// Writes next to `path` first and renames into place, which is atomic on the
+// same file system.
+fn replace_file(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    fs::write(&temp, bytes)?;
+    fs::rename(&temp, path)
+}
+
+/// Encodes bytes that are stored outside the data files, such as snapshot
+/// archives, the same way `write` would.
+pub fn seal(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    match load_config()? {
+        Some(config) => encrypt(&current_key(&config)?, bytes),
+        None => Ok(bytes.to_vec()),
+    }
+}
+
+/// Decodes bytes produced by `seal`.
+pub fn open(name: &str, bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+    if !bytes.starts_with(MAGIC) {
+        return Ok(bytes);
+    }
+    let config = load_config()?;
+    let key = config.as_ref().map(current_key).transpose()?;
+    decode(name, bytes, key.as_ref())
+}
+
+// Copies data files keep from before a schema migration, named like
+// "local_tasks_cache.json.v1.bak".
+fn migration_backups() -> Result<Vec<String>, String> {
+    let dir = profiles::data_dir()?;
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut names = Vec::new();
+    for entry in fs::read_dir(&dir).map_err(|e| format!("Failed to list profile directory: {}", e))? {
+        let entry = entry.map_err(|e| format!("Failed to list profile directory: {}", e))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let is_backup = DATA_FILES.iter().any(|file| {
+            name.strip_prefix(file)
+                .and_then(|rest| rest.strip_prefix(".v"))
+                .and_then(|rest| rest.strip_suffix(".bak"))
+                .is_some_and(|version| !version.is_empty() && version.bytes().all(|b| b.is_ascii_digit()))
+        });
+        if is_backup {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+/// Names of the files under a data directory, relative to the profile
+/// directory and separated with '/'.
+pub fn dir_files(dir: &str) -> Result<Vec<String>, String> {
+    fn walk(path: &std::path::Path, prefix: &str, names: &mut Vec<String>) -> Result<(), String> {
+        if !path.is_dir() {
+            return Ok(());
+        }
+        for entry in fs::read_dir(path).map_err(|e| format!("Failed to list {}: {}", prefix, e))? {
+            let entry = entry.map_err(|e| format!("Failed to list {}: {}", prefix, e))?;
+            let name = format!("{}/{}", prefix, entry.file_name().to_string_lossy());
+            if entry.path().is_dir() {
+                walk(&entry.path(), &name, names)?;
+            } else {
+                names.push(name);
+            }
+        }
+        Ok(())
+    }
+
+    let mut names = Vec::new();
+    walk(&profiles::data_file(dir)?, dir, &mut names)?;
+    names.sort();
+    Ok(names)
+}
+
+// Decodes every data file, migration backup and snapshot with `from`, then
+// writes them all back encoded with `to`. Nothing is written unless every
+// file could be decoded.
+fn rewrite_all(from: Option<&DataKey>, to: Option<&DataKey>) -> Result<(), String> {
+    let mut names: Vec<String> = DATA_FILES.iter().map(|n| n.to_string()).collect();
+    names.extend(migration_backups()?);
+    for dir in DATA_DIRS {
+        names.extend(dir_files(dir)?);
+    }
+    let mut paths = Vec::new();
+    for name in names {
+        paths.push((profiles::data_file(&name)?, name));
+    }
+    for path in snapshots::snapshot_paths()? {
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        paths.push((path, name));
+    }
+
+    let mut decoded = Vec::new();
+    for (path, name) in &paths {
+        if !path.exists() {
+            continue;
+        }
+        let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", name, e))?;
+        decoded.push((path, decode(name, bytes, from)?));
+    }
+
+    for (path, plaintext) in decoded {
+        let bytes = match to {
+            Some(key) => encrypt(key, &plaintext)?,
+            None => plaintext,
+        };
+        replace_file(path, &bytes).map_err(|e| format!("Failed to rewrite data file: {}", e))?;
+    }
+    Ok(())
+}
+
+#[command]
+pub fn get_encryption_status() -> Result<EncryptionStatus, String> {
+    let config = load_config()?;
+    let unlocked = match &config {
+        Some(config) => current_key(config).is_ok(),
+        None => true,
+    };
+
+    Ok(EncryptionStatus {
+        enabled: config.is_some(),
+        source: config.map(|c| c.source),
+        unlocked,
+    })
+}
+
+#[command]
+pub fn enable_encryption(source: KeySource, passphrase: Option<String>) -> Result<(), String> {
+    if load_config()?.is_some() {
+        return Err("Encryption is already enabled".to_string());
+    }
+
+    let (key, salt) = match source {
+        KeySource::Passphrase => {
+            let passphrase = passphrase
+                .filter(|p| !p.is_empty())
+                .ok_or_else(|| "A passphrase is required".to_string())?;
+            let mut salt = [0u8; 16];
+            OsRng.fill_bytes(&mut salt);
+            (derive_key(&passphrase, &salt, KDF_ITERATIONS), STANDARD.encode(salt))
+        }
+        KeySource::Keyring => {
+            let key: DataKey = Aes256Gcm::generate_key(OsRng).into();
+            keyring_entry()?
+                .set_password(&STANDARD.encode(key))
+                .map_err(|e| format!("Failed to store key in OS keyring: {}", e))?;
+            (key, String::new())
+        }
+    };
+
+    // The config goes first: reads cope with a mix of plaintext and encrypted
+    // files, so an interrupted re-encryption never locks data out.
+    save_config(&EncryptionConfig {
+        source,
+        salt,
+        iterations: KDF_ITERATIONS,
+        key_check: STANDARD.encode(encrypt(&key, KEY_CHECK)?),
+    })?;
+    cache_key(key);
+
+    rewrite_all(None, Some(&key))
+}
+
+#[command]
+pub fn disable_encryption() -> Result<(), String> {
+    let config = load_config()?.ok_or_else(|| "Encryption is not enabled".to_string())?;
+    let key = current_key(&config)?;
+
+    rewrite_all(Some(&key), None)?;
+
+    fs::remove_file(profiles::data_file(CONFIG_FILE)?)
+        .map_err(|e| format!("Failed to remove encryption config: {}", e))?;
+    if config.source == KeySource::Keyring {
+        keyring_entry()?
+            .delete_password()
+            .map_err(|e| format!("Failed to remove key from OS keyring: {}", e))?;
+    }
+    forget_key();
+    Ok(())
+}
+
+#[command]
+pub fn unlock_encryption(passphrase: String) -> Result<(), String> {
+    let config = load_config()?.ok_or_else(|| "Encryption is not enabled".to_string())?;
+    if config.source != KeySource::Passphrase {
+        return Err("Encryption key is stored in the OS keyring".to_string());
+    }
+
+    let salt = STANDARD
+        .decode(&config.salt)
+        .map_err(|e| format!("Invalid salt in encryption config: {}", e))?;
+    let key = derive_key(&passphrase, &salt, config.iterations);
+
+    let check = STANDARD
+        .decode(&config.key_check)
+        .map_err(|e| format!("Invalid key check in encryption config: {}", e))?;
+    match decrypt(&key, &check) {
+        Ok(plain) if plain == KEY_CHECK => {
+            cache_key(key);
+            Ok(())
+        }
+        _ => Err("Incorrect passphrase".to_string()),
+    }
+}
@@ -0,0 +1,460 @@
+// Local task helpers
+//
+// Subtasks point at their parent through parent_id. A task that has subtasks
+// is complete exactly when all of its subtasks are, so completion rolls up the
+// tree whenever tasks are saved.
+//
+// query_tasks filters, sorts and pages the task store in Rust so the UI only
+// receives the page it renders.
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use chrono::{DateTime, NaiveDate};
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+use crate::{journal, schema, timezones, Task};
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    #[default]
+    None,
+    Low,
+    Medium,
+    High,
+    Urgent,
+}
+
+// Clears one parent link in every cycle, taking the one on the most recently
+// updated task in it, which is normally the edit that closed the cycle.
+fn break_cycles(tasks: &mut [Task], index_by_id: &HashMap<String, usize>, now: &str) {
+    for start in 0..tasks.len() {
+        let mut path = vec![start];
+        let mut current = start;
+        while let Some(&parent) = tasks[current].parent_id.as_ref().and_then(|id| index_by_id.get(id)) {
+            if let Some(pos) = path.iter().position(|&i| i == parent) {
+                let newest = path[pos..]
+                    .iter()
+                    .copied()
+                    .max_by(|&a, &b| tasks[a].updated_at.cmp(&tasks[b].updated_at))
+                    .unwrap_or(current);
+                let task = &mut tasks[newest];
+                task.parent_id = None;
+                task.updated_at = Some(now.to_string());
+                break;
+            }
+            path.push(parent);
+            current = parent;
+        }
+    }
+}
+
+// Number of ancestors of each task. Parent links must be cycle-free.
+fn depths(tasks: &[Task], index_by_id: &HashMap<String, usize>) -> Vec<usize> {
+    tasks
+        .iter()
+        .map(|task| {
+            let mut depth = 0;
+            let mut current = task;
+            while let Some(&parent) = current.parent_id.as_ref().and_then(|id| index_by_id.get(id)) {
+                depth += 1;
+                current = &tasks[parent];
+            }
+            depth
+        })
+        .collect()
+}
+
+/// Clears parent links that point at missing tasks or form a cycle and updates
+/// the completion of every task that has subtasks. Tasks whose parent or state
+/// changes get a new updated_at so the change is picked up by sync.
+pub fn roll_up_completion(tasks: &mut [Task]) {
+    let ids: HashSet<String> = tasks.iter().map(|t| t.id.clone()).collect();
+    for task in tasks.iter_mut() {
+        if task.parent_id.as_ref().is_some_and(|id| !ids.contains(id) || *id == task.id) {
+            task.parent_id = None;
+        }
+    }
+
+    let index_by_id: HashMap<String, usize> = tasks
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (t.id.clone(), i))
+        .collect();
+    let now = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+    break_cycles(tasks, &index_by_id, &now);
+    let depths = depths(tasks, &index_by_id);
+
+    let mut children: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, task) in tasks.iter().enumerate() {
+        if let Some(&parent) = task.parent_id.as_ref().and_then(|id| index_by_id.get(id)) {
+            children.entry(parent).or_default().push(i);
+        }
+    }
+
+    // Deepest parents first, so nested subtasks are settled before their ancestors
+    let mut parents: Vec<usize> = children.keys().copied().collect();
+    parents.sort_by_key(|&i| std::cmp::Reverse(depths[i]));
+
+    for parent in parents {
+        let subtasks = &children[&parent];
+        let all_done = subtasks.iter().all(|&i| tasks[i].completed);
+        if tasks[parent].completed == all_done {
+            continue;
+        }
+
+        let completed_on = if all_done {
+            subtasks
+                .iter()
+                .filter_map(|&i| tasks[i].completed_on.clone())
+                .max()
+                .or_else(|| Some(now.clone()))
+        } else {
+            None
+        };
+
+        let task = &mut tasks[parent];
+        task.completed = all_done;
+        task.completed_on = completed_on;
+        task.updated_at = Some(now.clone());
+    }
+}
+
+/// Replaces the task store, journaling the change so it can be undone.
+pub fn store(tasks: &[Task]) -> Result<(), String> {
+    journal::record(journal::Store::Tasks, tasks)?;
+    schema::save(&schema::TASKS, tasks)
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct TaskFilter {
+    project: Option<String>,
+    completed: Option<bool>,
+    // Inclusive, YYYY-MM-DD
+    date_from: Option<String>,
+    date_to: Option<String>,
+    overdue: Option<bool>,
+    text: Option<String>,
+    min_priority: Option<Priority>,
+    // A task must carry every listed tag
+    tags: Vec<String>,
+    parent_id: Option<String>,
+    top_level_only: bool,
+    has_project: Option<bool>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    Date,
+    Title,
+    Project,
+    Priority,
+    CompletedOn,
+    UpdatedAt,
+}
+
+#[derive(Deserialize)]
+pub struct SortSpec {
+    key: SortKey,
+    #[serde(default)]
+    descending: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct TaskQuery {
+    filter: TaskFilter,
+    sort: Vec<SortSpec>,
+    // Offset of the page, as returned in next_cursor by the previous one
+    cursor: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct TaskPage {
+    tasks: Vec<Task>,
+    // Number of tasks matching the filter, across all pages
+    total: usize,
+    total_completed: usize,
+    total_overdue: usize,
+    next_cursor: Option<String>,
+}
+
+/// Parses a task date, which may be a bare date or a full ISO timestamp. A
+/// timestamp with an offset counts on the day it falls on in the user's zone.
+pub fn parse_task_date(date: &str) -> Option<NaiveDate> {
+    if date.len() > 10 {
+        if let Ok(instant) = DateTime::parse_from_rfc3339(date) {
+            return Some(instant.with_timezone(&timezones::user_zone()).date_naive());
+        }
+    }
+    date.get(..10)
+        .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+}
+
+pub fn is_overdue(task: &Task, today: NaiveDate) -> bool {
+    !task.completed && parse_task_date(&task.date).is_some_and(|date| date < today)
+}
+
+pub fn parse_bound(bound: &Option<String>) -> Result<Option<NaiveDate>, String> {
+    bound
+        .as_deref()
+        .filter(|b| !b.is_empty())
+        .map(|b| parse_task_date(b).ok_or_else(|| format!("Invalid date '{}'", b)))
+        .transpose()
+}
+
+fn matches(task: &Task, filter: &TaskFilter, from: Option<NaiveDate>, to: Option<NaiveDate>, today: NaiveDate) -> bool {
+    if filter.project.as_ref().is_some_and(|p| !task.project.eq_ignore_ascii_case(p)) {
+        return false;
+    }
+    if filter.completed.is_some_and(|c| task.completed != c) {
+        return false;
+    }
+    if filter.overdue.is_some_and(|o| is_overdue(task, today) != o) {
+        return false;
+    }
+    if filter.min_priority.is_some_and(|p| task.priority < p) {
+        return false;
+    }
+    if filter.parent_id.is_some() && task.parent_id != filter.parent_id {
+        return false;
+    }
+    if filter.top_level_only && task.parent_id.is_some() {
+        return false;
+    }
+    if filter.has_project.is_some_and(|h| task.project.trim().is_empty() == h) {
+        return false;
+    }
+    if !filter
+        .tags
+        .iter()
+        .all(|tag| task.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+    {
+        return false;
+    }
+
+    if from.is_some() || to.is_some() {
+        match parse_task_date(&task.date) {
+            Some(date) => {
+                if from.is_some_and(|from| date < from) || to.is_some_and(|to| date > to) {
+                    return false;
+                }
+            }
+            None => return false,
+        }
+    }
+
+    if let Some(text) = filter.text.as_ref().map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty()) {
+        let found = task.title.to_lowercase().contains(&text)
+            || task.description.to_lowercase().contains(&text)
+            || task.project.to_lowercase().contains(&text)
+            || task.tags.iter().any(|t| t.to_lowercase().contains(&text));
+        if !found {
+            return false;
+        }
+    }
+
+    true
+}
+
+// Empty values sort after everything else regardless of direction.
+fn compare_optional<T: Ord>(a: Option<T>, b: Option<T>, descending: bool) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) if descending => b.cmp(&a),
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn compare(a: &Task, b: &Task, spec: &SortSpec) -> Ordering {
+    let non_empty = |s: &str| if s.is_empty() { None } else { Some(s.to_lowercase()) };
+    match spec.key {
+        SortKey::Date => compare_optional(parse_task_date(&a.date), parse_task_date(&b.date), spec.descending),
+        SortKey::Title => compare_optional(non_empty(&a.title), non_empty(&b.title), spec.descending),
+        SortKey::Project => compare_optional(non_empty(&a.project), non_empty(&b.project), spec.descending),
+        SortKey::Priority => compare_optional(Some(a.priority), Some(b.priority), spec.descending),
+        SortKey::CompletedOn => compare_optional(a.completed_on.as_ref(), b.completed_on.as_ref(), spec.descending),
+        SortKey::UpdatedAt => compare_optional(a.updated_at.as_ref(), b.updated_at.as_ref(), spec.descending),
+    }
+}
+
+/// The tasks matching a filter, in their original order.
+pub fn filter_tasks(tasks: Vec<Task>, filter: &TaskFilter) -> Result<Vec<Task>, String> {
+    let today = timezones::today();
+    let from = parse_bound(&filter.date_from)?;
+    let to = parse_bound(&filter.date_to)?;
+
+    Ok(tasks
+        .into_iter()
+        .filter(|task| matches(task, filter, from, to, today))
+        .collect())
+}
+
+fn run_query(tasks: Vec<Task>, query: TaskQuery, today: NaiveDate) -> Result<TaskPage, String> {
+    let from = parse_bound(&query.filter.date_from)?;
+    let to = parse_bound(&query.filter.date_to)?;
+    let mut matching: Vec<Task> = tasks
+        .into_iter()
+        .filter(|task| matches(task, &query.filter, from, to, today))
+        .collect();
+
+    let sort = if query.sort.is_empty() {
+        vec![
+            SortSpec { key: SortKey::Date, descending: false },
+            SortSpec { key: SortKey::Title, descending: false },
+        ]
+    } else {
+        query.sort
+    };
+    matching.sort_by(|a, b| {
+        sort.iter()
+            .map(|spec| compare(a, b, spec))
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    });
+
+    let total = matching.len();
+    let total_completed = matching.iter().filter(|t| t.completed).count();
+    let total_overdue = matching.iter().filter(|t| is_overdue(t, today)).count();
+
+    let offset = match query.cursor.as_deref() {
+        Some(cursor) => cursor
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid cursor '{}'", cursor))?,
+        None => 0,
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let end = offset.saturating_add(limit).min(total);
+    let next_cursor = if end < total { Some(end.to_string()) } else { None };
+
+    let tasks = matching.into_iter().skip(offset).take(limit).collect();
+
+    Ok(TaskPage {
+        tasks,
+        total,
+        total_completed,
+        total_overdue,
+        next_cursor,
+    })
+}
+
+#[command]
+pub fn query_tasks(query: TaskQuery) -> Result<TaskPage, String> {
+    let tasks: Vec<Task> = schema::load(&schema::TASKS)?;
+    run_query(tasks, query, timezones::today())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn task(id: &str, title: &str, date: &str) -> Task {
+        Task {
+            id: id.to_string(),
+            title: title.to_string(),
+            date: date.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn query(value: serde_json::Value) -> TaskQuery {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn ids(page: &TaskPage) -> Vec<&str> {
+        page.tasks.iter().map(|t| t.id.as_str()).collect()
+    }
+
+    #[test]
+    fn filters_sorts_and_pages() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let mut done = task("4", "Done", "2024-05-01");
+        done.completed = true;
+        let mut urgent = task("3", "Urgent", "2024-05-20");
+        urgent.priority = Priority::Urgent;
+        let tasks = vec![task("1", "Late", "2024-05-01"), task("2", "Soon", "2024-05-12"), urgent, done];
+
+        let first = run_query(
+            tasks.clone(),
+            query(json!({ "filter": { "completed": false }, "sort": [{ "key": "priority", "descending": true }], "limit": 2 })),
+            today,
+        )
+        .unwrap();
+        assert_eq!(first.total, 3);
+        assert_eq!(first.total_overdue, 1);
+        assert_eq!(ids(&first)[0], "3");
+        assert_eq!(first.next_cursor.as_deref(), Some("2"));
+
+        let second = run_query(
+            tasks.clone(),
+            query(json!({ "filter": { "completed": false }, "cursor": "2", "limit": 2 })),
+            today,
+        )
+        .unwrap();
+        assert_eq!(second.tasks.len(), 1);
+        assert_eq!(second.next_cursor, None);
+
+        let ranged = run_query(
+            tasks,
+            query(json!({ "filter": { "date_from": "2024-05-02", "date_to": "2024-05-15" } })),
+            today,
+        )
+        .unwrap();
+        assert_eq!(ids(&ranged), vec!["2"]);
+    }
+
+    #[test]
+    fn huge_cursor_returns_an_empty_page() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let tasks = vec![task("1", "Only", "2024-05-01")];
+        let page = run_query(tasks, query(json!({ "cursor": usize::MAX.to_string() })), today).unwrap();
+        assert!(page.tasks.is_empty());
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[test]
+    fn rolls_up_subtask_completion() {
+        let mut parent = task("p", "Parent", "");
+        parent.completed = true;
+        let mut first = task("a", "First", "");
+        first.parent_id = Some("p".to_string());
+        first.completed = true;
+        let mut second = task("b", "Second", "");
+        second.parent_id = Some("p".to_string());
+        let mut tasks = vec![parent, first, second];
+
+        roll_up_completion(&mut tasks);
+        assert!(!tasks[0].completed);
+
+        tasks[2].completed = true;
+        tasks[2].completed_on = Some("2024-05-02T10:00:00.000Z".to_string());
+        roll_up_completion(&mut tasks);
+        assert!(tasks[0].completed);
+        assert_eq!(tasks[0].completed_on.as_deref(), Some("2024-05-02T10:00:00.000Z"));
+    }
+
+    #[test]
+    fn drops_the_newest_link_of_a_cycle() {
+        let mut a = task("a", "A", "");
+        a.parent_id = Some("b".to_string());
+        a.updated_at = Some("2024-05-01T00:00:00.000Z".to_string());
+        let mut b = task("b", "B", "");
+        b.parent_id = Some("a".to_string());
+        b.updated_at = Some("2024-05-02T00:00:00.000Z".to_string());
+        let mut tasks = vec![a, b];
+
+        roll_up_completion(&mut tasks);
+        assert_eq!(tasks[0].parent_id.as_deref(), Some("b"));
+        assert_eq!(tasks[1].parent_id, None);
+    }
+}
@@ -0,0 +1,291 @@
+// Time tracking
+//
+// Time entries record work against a task or a project folder. An entry
+// without ended_at is the running timer; starting a new one stops it. Entries
+// carry updated_at and pending_sync so the frontend syncs them with Firestore
+// the same way as tasks.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::command;
+
+use crate::schema::{self, Schema};
+use crate::{projects, stats, Task};
+
+pub const TIME_ENTRIES: Schema = Schema {
+    file: "time_entries.json",
+    version: 1,
+    migrations: &[schema::wrap_items],
+};
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TimeEntry {
+    pub id: String,
+    pub task_id: Option<String>,
+    // Full path of a folder listed by get_project_folders
+    pub folder_path: Option<String>,
+    pub project_id: Option<String>,
+    // Project name, or the folder name for folders without a project
+    pub project: String,
+    pub note: String,
+    pub started_at: String,
+    // None while the timer runs
+    pub ended_at: Option<String>,
+    pub updated_at: Option<String>,
+    pub pending_sync: bool,
+}
+
+#[derive(Serialize)]
+pub struct ProjectTime {
+    project: String,
+    project_id: Option<String>,
+    seconds: i64,
+}
+
+#[derive(Serialize)]
+pub struct WeekReport {
+    // Monday of the week
+    week_start: String,
+    projects: Vec<ProjectTime>,
+    total_seconds: i64,
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| format!("Invalid timestamp '{}': {}", value, e))
+}
+
+pub fn load_entries() -> Result<Vec<TimeEntry>, String> {
+    schema::load(&TIME_ENTRIES)
+}
+
+// Fills in the project an entry counts towards from its task or folder.
+fn resolve_project(entry: &mut TimeEntry) -> Result<(), String> {
+    if let Some(task_id) = &entry.task_id {
+        let tasks: Vec<Task> = schema::load(&schema::TASKS)?;
+        let task = tasks
+            .iter()
+            .find(|t| t.id == *task_id)
+            .ok_or_else(|| format!("Task '{}' does not exist", task_id))?;
+        entry.project_id = task.project_id.clone();
+        entry.project = task.project.clone();
+    } else if let Some(folder) = &entry.folder_path {
+        let path = Path::new(folder);
+        let projects = projects::load_projects()?;
+        entry.project_id = projects::project_for_folder(&projects, path);
+        entry.project = match entry.project_id.as_ref().and_then(|id| projects.iter().find(|p| p.id == *id)) {
+            Some(project) => project.name.clone(),
+            None => path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or(folder)
+                .to_string(),
+        };
+    } else {
+        return Err("A time entry needs a task or a project folder".to_string());
+    }
+    Ok(())
+}
+
+fn validate(entry: &TimeEntry) -> Result<(), String> {
+    let started = parse_time(&entry.started_at)?;
+    if let Some(ended) = &entry.ended_at {
+        if parse_time(ended)? < started {
+            return Err("A time entry cannot end before it starts".to_string());
+        }
+    }
+    Ok(())
+}
+
+fn stop_running(entries: &mut [TimeEntry]) -> Option<TimeEntry> {
+    let timestamp = now();
+    let mut stopped = None;
+    for entry in entries.iter_mut().filter(|e| e.ended_at.is_none()) {
+        entry.ended_at = Some(timestamp.clone());
+        entry.updated_at = Some(timestamp.clone());
+        entry.pending_sync = true;
+        stopped = Some(entry.clone());
+    }
+    stopped
+}
+
+fn duration_secs(entry: &TimeEntry, now: DateTime<Utc>) -> Result<i64, String> {
+    let started = parse_time(&entry.started_at)?;
+    let ended = match &entry.ended_at {
+        Some(ended) => parse_time(ended)?,
+        None => now,
+    };
+    Ok((ended - started).num_seconds().max(0))
+}
+
+fn parse_bound(bound: &Option<String>) -> Result<Option<NaiveDate>, String> {
+    bound
+        .as_deref()
+        .filter(|b| !b.is_empty())
+        .map(|b| NaiveDate::parse_from_str(b, "%Y-%m-%d").map_err(|_| format!("Invalid date '{}'", b)))
+        .transpose()
+}
+
+// Weekly totals per project for entries starting between from and to (inclusive).
+fn weekly_report(entries: &[TimeEntry], from: Option<NaiveDate>, to: Option<NaiveDate>) -> Result<Vec<WeekReport>, String> {
+    let now = Utc::now();
+    let mut weeks: BTreeMap<NaiveDate, HashMap<(String, Option<String>), i64>> = BTreeMap::new();
+
+    for entry in entries {
+        let day = parse_time(&entry.started_at)?.with_timezone(&Local).date_naive();
+        if from.is_some_and(|from| day < from) || to.is_some_and(|to| day > to) {
+            continue;
+        }
+        *weeks
+            .entry(stats::week_start(day))
+            .or_default()
+            .entry((entry.project.clone(), entry.project_id.clone()))
+            .or_default() += duration_secs(entry, now)?;
+    }
+
+    Ok(weeks
+        .into_iter()
+        .map(|(week, totals)| {
+            let mut projects: Vec<ProjectTime> = totals
+                .into_iter()
+                .map(|((project, project_id), seconds)| ProjectTime { project, project_id, seconds })
+                .collect();
+            projects.sort_by(|a, b| b.seconds.cmp(&a.seconds).then_with(|| a.project.cmp(&b.project)));
+            WeekReport {
+                week_start: week.format("%Y-%m-%d").to_string(),
+                total_seconds: projects.iter().map(|p| p.seconds).sum(),
+                projects,
+            }
+        })
+        .collect())
+}
+
+#[command]
+pub fn list_time_entries() -> Result<Vec<TimeEntry>, String> {
+    load_entries()
+}
+
+/// Replaces the stored entries; used by Firestore sync.
+#[command]
+pub fn save_time_entries(entries: Vec<TimeEntry>) -> Result<(), String> {
+    for entry in &entries {
+        validate(entry)?;
+    }
+    schema::save(&TIME_ENTRIES, &entries)
+}
+
+/// Starts a timer on a task or project folder, stopping any running one.
+#[command]
+pub fn start_time_entry(task_id: Option<String>, folder_path: Option<String>, note: Option<String>) -> Result<TimeEntry, String> {
+    let timestamp = now();
+    let mut entry = TimeEntry {
+        id: uuid::Uuid::new_v4().to_string(),
+        task_id,
+        folder_path,
+        note: note.unwrap_or_default(),
+        started_at: timestamp.clone(),
+        updated_at: Some(timestamp),
+        pending_sync: true,
+        ..Default::default()
+    };
+    resolve_project(&mut entry)?;
+
+    let mut entries = load_entries()?;
+    stop_running(&mut entries);
+    entries.push(entry.clone());
+    schema::save(&TIME_ENTRIES, &entries)?;
+    Ok(entry)
+}
+
+/// Stops the running timer. Returns the stopped entry, if one was running.
+#[command]
+pub fn stop_time_entry() -> Result<Option<TimeEntry>, String> {
+    let mut entries = load_entries()?;
+    let stopped = stop_running(&mut entries);
+    if stopped.is_some() {
+        schema::save(&TIME_ENTRIES, &entries)?;
+    }
+    Ok(stopped)
+}
+
+/// Adds a finished entry by hand.
+#[command]
+pub fn add_time_entry(mut entry: TimeEntry) -> Result<TimeEntry, String> {
+    if entry.ended_at.is_none() {
+        return Err("A manual time entry needs an end time".to_string());
+    }
+    validate(&entry)?;
+    resolve_project(&mut entry)?;
+    if entry.id.is_empty() {
+        entry.id = uuid::Uuid::new_v4().to_string();
+    }
+    entry.updated_at = Some(now());
+    entry.pending_sync = true;
+
+    let mut entries = load_entries()?;
+    entries.push(entry.clone());
+    schema::save(&TIME_ENTRIES, &entries)?;
+    Ok(entry)
+}
+
+#[command]
+pub fn update_time_entry(mut entry: TimeEntry) -> Result<TimeEntry, String> {
+    validate(&entry)?;
+    resolve_project(&mut entry)?;
+    entry.updated_at = Some(now());
+    entry.pending_sync = true;
+
+    let mut entries = load_entries()?;
+    let existing = entries
+        .iter_mut()
+        .find(|e| e.id == entry.id)
+        .ok_or_else(|| format!("Time entry '{}' does not exist", entry.id))?;
+    *existing = entry.clone();
+    schema::save(&TIME_ENTRIES, &entries)?;
+    Ok(entry)
+}
+
+#[command]
+pub fn delete_time_entry(id: String) -> Result<(), String> {
+    let mut entries = load_entries()?;
+    let before = entries.len();
+    entries.retain(|e| e.id != id);
+    if entries.len() == before {
+        return Err(format!("Time entry '{}' does not exist", id));
+    }
+    schema::save(&TIME_ENTRIES, &entries)
+}
+
+#[command]
+pub fn get_time_report(from: Option<String>, to: Option<String>) -> Result<Vec<WeekReport>, String> {
+    weekly_report(&load_entries()?, parse_bound(&from)?, parse_bound(&to)?)
+}
+
+#[command]
+pub fn export_time_report_csv(path: String, from: Option<String>, to: Option<String>) -> Result<(), String> {
+    let report = get_time_report(from, to)?;
+
+    let write_err = |e: csv::Error| format!("Failed to write time report: {}", e);
+    let mut writer = csv::Writer::from_path(&path).map_err(write_err)?;
+    writer.write_record(["week_start", "project", "hours"]).map_err(write_err)?;
+    for week in &report {
+        for project in &week.projects {
+            writer
+                .write_record([
+                    week.week_start.as_str(),
+                    project.project.as_str(),
+                    &format!("{:.2}", project.seconds as f64 / 3600.0),
+                ])
+                .map_err(write_err)?;
+        }
+    }
+    writer.flush().map_err(|e| format!("Failed to write time report: {}", e))
+}
@@ -0,0 +1,185 @@
+// Timezones
+//
+// Local events keep their dates and times as wall-clock strings plus the IANA
+// zone they were entered in; Google events carry an offset or are all-day
+// dates. Everything time-sensitive goes through here to turn those into
+// instants, so comparisons are done on absolute time and stay correct across
+// DST changes and when the user travels. Wall-clock times that fall in a DST
+// gap move forward to the first valid time; ambiguous ones take the earlier.
+// The system zone is looked up at most once a minute.
+
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use chrono_tz::Tz;
+use serde::Serialize;
+use tauri::command;
+
+use crate::tasks::parse_task_date;
+use crate::Event;
+
+// Local events with a start time but no end time are assumed to last this long
+pub const DEFAULT_DURATION_MINUTES: i64 = 60;
+// How long a detected zone is reused, so a zone change while travelling is
+// still picked up
+const ZONE_REFRESH: std::time::Duration = std::time::Duration::from_secs(60);
+
+static SYSTEM_ZONE: Mutex<Option<(Instant, Option<Tz>)>> = Mutex::new(None);
+
+#[derive(Serialize)]
+pub struct EventTimes {
+    timezone: String,
+    user_timezone: String,
+    all_day: bool,
+    // RFC 3339, in the event's zone and in the user's
+    start: String,
+    end: String,
+    user_start: String,
+    user_end: String,
+}
+
+pub fn parse_zone(name: &str) -> Result<Tz, String> {
+    name.trim().parse::<Tz>().map_err(|_| format!("Unknown timezone '{}'", name))
+}
+
+/// The zone the system is set to, None if it cannot be determined.
+pub fn system_zone() -> Option<Tz> {
+    // The cache holds plain values that a panic can't leave half-written
+    let mut cached = SYSTEM_ZONE.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some((at, zone)) = *cached {
+        if at.elapsed() < ZONE_REFRESH {
+            return zone;
+        }
+    }
+    let zone = iana_time_zone::get_timezone()
+        .ok()
+        .and_then(|name| parse_zone(&name).ok());
+    *cached = Some((Instant::now(), zone));
+    zone
+}
+
+/// The zone the system is set to, UTC if it cannot be determined.
+pub fn user_zone() -> Tz {
+    system_zone().unwrap_or(Tz::UTC)
+}
+
+/// Today's date in the user's zone.
+pub fn today() -> NaiveDate {
+    chrono::Utc::now().with_timezone(&user_zone()).date_naive()
+}
+
+pub fn user_zone_name() -> String {
+    user_zone().name().to_string()
+}
+
+/// The event's zone; events without one are in the user's zone.
+pub fn event_zone(event: &Event) -> Tz {
+    event
+        .timezone
+        .as_deref()
+        .and_then(|name| parse_zone(name).ok())
+        .unwrap_or_else(user_zone)
+}
+
+/// A wall-clock time in a zone.
+pub fn resolve(zone: Tz, datetime: NaiveDateTime) -> Option<DateTime<Tz>> {
+    zone.from_local_datetime(&datetime).earliest().or_else(|| {
+        // In a DST gap; no gap is longer than an hour
+        (1..=4).find_map(|quarter| {
+            zone.from_local_datetime(&(datetime + Duration::minutes(15 * quarter)))
+                .earliest()
+        })
+    })
+}
+
+pub fn midnight(zone: Tz, date: NaiveDate) -> Option<DateTime<Tz>> {
+    resolve(zone, date.and_time(NaiveTime::MIN))
+}
+
+pub fn parse_time(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M:%S"))
+        .ok()
+}
+
+/// Start and exclusive end of a local event in its zone, and whether it is
+/// all-day. Events without a start time take up whole days.
+pub fn event_span(event: &Event) -> Option<(DateTime<Tz>, DateTime<Tz>, bool)> {
+    let zone = event_zone(event);
+    let start_date = event.date_start.as_deref().and_then(parse_task_date)?;
+    let end_date = event
+        .date_end
+        .as_deref()
+        .and_then(parse_task_date)
+        .unwrap_or(start_date)
+        .max(start_date);
+
+    let Some(start_time) = event.time_start.as_deref().and_then(parse_time) else {
+        let start = midnight(zone, start_date)?;
+        let end = midnight(zone, end_date + Duration::days(1))?;
+        return Some((start, end, true));
+    };
+
+    let start = resolve(zone, start_date.and_time(start_time))?;
+    let end = match event.time_end.as_deref().and_then(parse_time) {
+        Some(end_time) => {
+            let end = resolve(zone, end_date.and_time(end_time))?;
+            if end > start {
+                end
+            } else {
+                // An end time before the start on the same day runs past midnight
+                resolve(zone, (end_date + Duration::days(1)).and_time(end_time))?
+            }
+        }
+        None => start + Duration::minutes(DEFAULT_DURATION_MINUTES),
+    };
+    Some((start, end, false))
+}
+
+pub fn event_start(event: &Event) -> Option<DateTime<Tz>> {
+    event_span(event).filter(|(_, _, all_day)| !all_day).map(|(start, _, _)| start)
+}
+
+#[command]
+pub fn get_user_timezone() -> String {
+    user_zone_name()
+}
+
+#[command]
+pub fn list_timezones() -> Vec<&'static str> {
+    chrono_tz::TZ_VARIANTS.iter().map(|zone| zone.name()).collect()
+}
+
+/// When a local event starts and ends, in its own zone and in the user's.
+#[command]
+pub fn get_event_times(event_id: String) -> Result<EventTimes, String> {
+    let event = crate::load_local_events()?
+        .into_iter()
+        .find(|e| e.id == event_id)
+        .ok_or_else(|| format!("Event '{}' does not exist", event_id))?;
+    let (start, end, all_day) =
+        event_span(&event).ok_or_else(|| format!("'{}' has no valid start date", event.title))?;
+
+    let user = user_zone();
+    Ok(EventTimes {
+        timezone: event_zone(&event).name().to_string(),
+        user_timezone: user.name().to_string(),
+        all_day,
+        start: start.to_rfc3339(),
+        end: end.to_rfc3339(),
+        user_start: start.with_timezone(&user).to_rfc3339(),
+        user_end: end.with_timezone(&user).to_rfc3339(),
+    })
+}
+
+/// Converts a wall-clock time ("YYYY-MM-DDTHH:MM") from one zone to another.
+#[command]
+pub fn convert_time(datetime: String, from_zone: String, to_zone: String) -> Result<String, String> {
+    let naive = NaiveDateTime::parse_from_str(&datetime, "%Y-%m-%dT%H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(&datetime, "%Y-%m-%dT%H:%M:%S"))
+        .map_err(|_| format!("Invalid date and time '{}'", datetime))?;
+    let from = parse_zone(&from_zone)?;
+    let to = parse_zone(&to_zone)?;
+    let instant = resolve(from, naive).ok_or_else(|| format!("{} does not exist in {}", datetime, from_zone))?;
+    Ok(instant.with_timezone(&to).to_rfc3339())
+}
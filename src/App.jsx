@@ -1,4 +1,4 @@
-import React, { useEffect } from "react";
+import React, { useEffect, useState } from "react";
 import {
   BrowserRouter as Router,
   Routes,
@@ -6,10 +6,14 @@ import {
   useLocation,
 } from "react-router-dom";
 import { GoogleOAuthProvider } from "@react-oauth/google";
+import { listen } from "@tauri-apps/api/event";
+import { invoke } from "@tauri-apps/api/tauri";
+import { sendNotification } from "@tauri-apps/api/notification";
 import { AuthProvider } from "./utils/AuthContext";
 
 import Sidebar from "./components/Sidebar";
 import Navbar from "./components/Navbar";
+import UnlockScreen from "./components/UnlockScreen";
 
 // Screens
 import Home from "./screens/Home";
@@ -27,6 +31,19 @@ const EmptyHome = () => null;
 const AppContent = () => {
   const location = useLocation();
 
+  // Travel reminders from the backend scheduler
+  useEffect(() => {
+    const unlisten = listen("leave-now", ({ payload }) => {
+      sendNotification({
+        title: `Leave now for ${payload.title}`,
+        body: `${payload.travel_minutes} min from ${payload.origin}`,
+      });
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
   return (
     <div
       data-tauri-drag-region
@@ -80,6 +97,25 @@ const AppContent = () => {
 };
 
 const App = () => {
+  // Null until the encryption status is known
+  const [locked, setLocked] = useState(null);
+
+  useEffect(() => {
+    const checkLocked = () =>
+      invoke("get_encryption_status")
+        .then((status) => setLocked(status.enabled && !status.unlocked))
+        .catch((error) => {
+          console.error("Failed to read encryption status:", error);
+          setLocked(false);
+        });
+
+    checkLocked();
+    const unlisten = listen("profile-changed", checkLocked);
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
   useEffect(() => {
     const disableContextMenu = (e) => {
       e.preventDefault();
@@ -103,7 +139,11 @@ const App = () => {
             v7_relativeSplatPath: true,
           }}
         >
-          <AppContent />
+          {locked === null ? null : locked ? (
+            <UnlockScreen onUnlock={() => setLocked(false)} />
+          ) : (
+            <AppContent />
+          )}
         </Router>
       </AuthProvider>
     </GoogleOAuthProvider>
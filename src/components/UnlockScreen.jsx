@@ -0,0 +1,68 @@
+import { useState } from "react";
+import { invoke } from "@tauri-apps/api/tauri";
+import { Lock } from "lucide-react";
+
+// Shown instead of the app while data is encrypted with a passphrase that
+// has not been entered yet.
+const UnlockScreen = ({ onUnlock }) => {
+  const [passphrase, setPassphrase] = useState("");
+  const [error, setError] = useState(null);
+  const [unlocking, setUnlocking] = useState(false);
+
+  const handleUnlock = async (e) => {
+    e.preventDefault();
+    if (!passphrase || unlocking) return;
+
+    setUnlocking(true);
+    try {
+      await invoke("unlock_encryption", { passphrase });
+      setPassphrase("");
+      onUnlock();
+    } catch (err) {
+      setError(String(err));
+    } finally {
+      setUnlocking(false);
+    }
+  };
+
+  return (
+    <div
+      data-tauri-drag-region
+      className="h-screen flex items-center justify-center rounded-3xl overflow-hidden bg-gray-950/80 font-roboto"
+    >
+      <form
+        onSubmit={handleUnlock}
+        className="bg-black/70 rounded-3xl border border-white/10 shadow-2xl p-8 w-full max-w-md space-y-4"
+      >
+        <div className="flex flex-col items-center text-white">
+          <Lock className="w-8 h-8 mb-3 text-cyan-200" />
+          <h2 className="text-2xl font-bold">Unlock your data</h2>
+          <span className="text-sm text-white/60 mt-1">
+            Enter the passphrase used to encrypt this profile
+          </span>
+        </div>
+        <input
+          type="password"
+          autoFocus
+          value={passphrase}
+          onChange={(e) => {
+            setPassphrase(e.target.value);
+            setError(null);
+          }}
+          placeholder="Passphrase"
+          className="w-full bg-gray-800/30 text-white p-3 rounded-lg border border-gray-700/50 focus:border-white/30 focus:ring-1 focus:ring-white placeholder-cyan-200/30 transition-all outline-none"
+        />
+        {error && <p className="text-sm text-red-400">{error}</p>}
+        <button
+          type="submit"
+          disabled={!passphrase || unlocking}
+          className="w-full px-6 py-2 rounded-lg bg-cyan-500/20 text-cyan-200 hover:bg-cyan-500/30 hover:text-white border border-cyan-400 transition-all duration-300 disabled:opacity-50"
+        >
+          {unlocking ? "Unlocking..." : "Unlock"}
+        </button>
+      </form>
+    </div>
+  );
+};
+
+export default UnlockScreen;
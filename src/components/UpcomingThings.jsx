@@ -8,59 +8,29 @@ const UpcomingThings = () => {
 
   const loadUpcomingThings = async () => {
     try {
-      const loadedLocalTasks = await invoke("load_local_tasks");
-      let loadedAsanaTasks = await invoke("read_asana_tasks_cache");
-      const loadedLocalEvents = await invoke("load_local_events");
-      const loadedGoogleEvents =
-        JSON.parse(localStorage.getItem("cached_events")) || [];
-
-      try {
-        loadedAsanaTasks = JSON.parse(loadedAsanaTasks);
-      } catch (error) {
-        console.error("Failed to parse Asana tasks:", error);
-        loadedAsanaTasks = [];
-      }
-
-      const allTasks = [
-        ...(loadedLocalTasks?.map((task) => ({
-          summary: task.title,
-          date: new Date(task.date),
-          type: "local_task",
-          completed: task.completed,
-        })) || []),
-        ...(loadedAsanaTasks?.map((task) => ({
-          summary: task.name,
-          date: new Date(task.due_on),
-          type: "asana_task",
-          completed: task.completed,
-        })) || []),
-      ];
-
-      const allEvents = [
-        ...(loadedLocalEvents?.map((event) => ({
-          summary: event.title,
-          date: new Date(event.date_start),
-          type: "local_event",
-        })) || []),
-        ...(loadedGoogleEvents
-          ?.filter((event) => event.summary !== "Weekly Huddle")
-          .map((event) => ({
-            summary: event.summary,
-            date: new Date(event.start),
-            type: "google_event",
-          })) || []),
-      ];
-
-      const sortedTasks = allTasks
-        .filter((task) => task.date > new Date() && !task.completed)
-        .sort((a, b) => a.date - b.date);
-
-      const startOfToday = new Date();
-      startOfToday.setHours(0, 0, 0, 0);
-
-      const sortedEvents = allEvents
-        .filter((event) => event.date >= startOfToday)
-        .sort((a, b) => a.date - b.date);
+      // One merged timeline of local and Asana tasks, local and Google events
+      const agenda = await invoke("get_agenda", {
+        options: { all_upcoming: true, include_overdue: false },
+      });
+      const now = new Date();
+      const toCard = (item) => ({
+        summary: item.title,
+        date: new Date(item.start || item.date),
+        type: item.kind,
+      });
+
+      const sortedTasks = agenda.items
+        .filter((item) => item.kind.endsWith("_task"))
+        .map(toCard);
+
+      const sortedEvents = agenda.items
+        .filter(
+          (item) =>
+            item.kind.endsWith("_event") &&
+            item.title !== "Weekly Huddle" &&
+            (!item.end || new Date(item.end) > now)
+        )
+        .map(toCard);
 
       if (sortedEvents.length > 0 && sortedTasks.length > 0) {
         setFirstCard(sortedEvents[0]);
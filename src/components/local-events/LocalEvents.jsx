@@ -144,7 +144,11 @@ const LocalEvents = ({ isLoaded }) => {
 
   const saveEvents = async (updatedEvents) => {
     try {
-      await invoke("save_local_events", { events: updatedEvents });
+      // The backend fills in coordinates or addresses it could resolve
+      const savedEvents = await invoke("save_local_events", {
+        events: updatedEvents,
+      });
+      setEvents(savedEvents);
       eventBus.emit("events_updated");
     } catch (error) {
       console.error("Error saving events:", error);
@@ -1,5 +1,7 @@
 import { useState, useRef, useEffect } from "react";
 import { v4 as uuidv4 } from "uuid";
+import { invoke } from "@tauri-apps/api/tauri";
+import { confirm } from "@tauri-apps/api/dialog";
 import eventBus from "../../utils/eventBus";
 import usePlacesAutocomplete, {
   getGeocode,
@@ -189,9 +191,27 @@ const NewLocalEventModal = ({
     const newEventEntry = {
       id: uuidv4(),
       ...newEvent,
+      timezone: Intl.DateTimeFormat().resolvedOptions().timeZone,
       updated_at: new Date().toISOString(),
       pending_sync: !isOnline,
     };
+
+    // Warn before double-booking an existing local or Google event
+    try {
+      const conflicts = await invoke("check_event_conflicts", {
+        event: newEventEntry,
+      });
+      if (conflicts.length > 0) {
+        const titles = conflicts.map((c) => `"${c.second.title}"`).join(", ");
+        const proceed = await confirm(
+          `This event overlaps ${titles}. Save it anyway?`,
+          { title: "Event conflict", type: "warning" }
+        );
+        if (!proceed) return;
+      }
+    } catch (error) {
+      console.error("Error checking event conflicts:", error);
+    }
     const updatedEvents = [...events, newEventEntry];
     setEvents(updatedEvents);
     await saveEvents(updatedEvents);
@@ -11,6 +11,7 @@ import { v4 as uuidv4 } from "uuid";
 import { invoke } from "@tauri-apps/api/tauri";
 import { shell } from "@tauri-apps/api";
 import { syncLocalTasksWithFirestore } from "../../utils/syncLocalTasks";
+import { loadProjectNames } from "../../utils/buildProjectOptions";
 import { db } from "../../config/firebase";
 import { doc, deleteDoc, setDoc, writeBatch } from "firebase/firestore";
 import { FaCopy } from "react-icons/fa";
@@ -68,7 +69,12 @@ const LocalTasks = ({ setIsTaskAvailable }) => {
 
   const saveTasks = async (updatedTasks) => {
     try {
-      await invoke("save_local_tasks", { tasks: updatedTasks });
+      // The backend rolls subtask completion up to parents and archives old tasks
+      const savedTasks = await invoke("save_local_tasks", {
+        tasks: updatedTasks,
+      });
+      setTasks(savedTasks);
+      return savedTasks;
     } catch (error) {
       console.error("Error saving tasks:", error);
     }
@@ -123,10 +129,9 @@ const LocalTasks = ({ setIsTaskAvailable }) => {
   }, [tasks]);
 
   useEffect(() => {
-    const distinctProjects = [
-      ...new Set(tasks.map((t) => t.project).filter(Boolean)),
-    ];
-    setProjects(distinctProjects);
+    loadProjectNames()
+      .then(setProjects)
+      .catch((error) => console.error("Error loading projects:", error));
   }, [tasks]);
 
   const handleContainerClick = () => {
@@ -166,8 +171,13 @@ const LocalTasks = ({ setIsTaskAvailable }) => {
       project: newTask.project || "",
       completed: false,
       completed_on: null,
+      created_at: new Date().toISOString(),
       updated_at: new Date().toISOString(),
       pending_sync: !isOnline,
+      priority: newTask.priority || "none",
+      tags: newTask.tags || [],
+      estimated_minutes: newTask.estimated_minutes ?? null,
+      parent_id: newTask.parent_id ?? null,
     };
 
     const updatedTasks = [...tasks, newTaskEntry];
@@ -192,7 +202,7 @@ const LocalTasks = ({ setIsTaskAvailable }) => {
 
       // 5) Also call Tauri `invoke` in background
       try {
-        await invoke("save_local_tasks", { tasks: updatedTasks });
+        await saveTasks(updatedTasks);
         eventBus.emit("events_updated");
       } catch (error) {
         console.error("Error invoking Tauri save:", error);
@@ -472,14 +482,6 @@ const LocalTasks = ({ setIsTaskAvailable }) => {
     }
   };
 
-  const distinctIncompleteProjects = useMemo(() => {
-    const incompleteProjects = tasks
-      .filter((t) => !t.completed)
-      .map((t) => t.project);
-
-    return [...new Set(incompleteProjects)].filter(Boolean);
-  }, [tasks]);
-
   const deleteTaskById = async (taskId) => {
     const updatedTasks = tasks.filter((task) => task.id !== taskId);
     setTasks(updatedTasks);
@@ -691,7 +693,7 @@ const LocalTasks = ({ setIsTaskAvailable }) => {
           taskIsComplete={taskIsComplete}
           handleTaskComplete={handleTaskComplete}
           processTaskDescription={processTaskDescription}
-          projects={distinctIncompleteProjects}
+          projects={projects}
           setProjects={setProjects}
           isOnline={isOnline}
         />
@@ -709,7 +711,7 @@ const LocalTasks = ({ setIsTaskAvailable }) => {
           handleContainerClick={handleContainerClick}
           notification={notification}
           setNotification={setNotification}
-          projects={distinctIncompleteProjects}
+          projects={projects}
           setProjects={setProjects}
         />
       )}
@@ -10,6 +10,7 @@ import {
   Eye,
 } from "lucide-react";
 import AutocompleteInput from "./AutoCompleteInput";
+import SuggestedSlots from "./SuggestedSlots";
 
 const SelectedLocalTaskModal = ({
   tasks,
@@ -142,6 +143,8 @@ const SelectedLocalTaskModal = ({
                     setSelectedTask({
                       ...selectedTask,
                       project: newValue,
+                      // Relinked by name when saved
+                      project_id: null,
                     })
                   }
                   projects={projects}
@@ -193,6 +196,18 @@ const SelectedLocalTaskModal = ({
             </div>
           </div>
 
+          {!taskIsComplete && (
+            <SuggestedSlots
+              task={selectedTask}
+              onPick={(slot) =>
+                setSelectedTask({
+                  ...selectedTask,
+                  date: slot.start.slice(0, 10),
+                })
+              }
+            />
+          )}
+
           {/* ---- DESCRIPTION (Edit or View) ---- */}
           <div
             className={`${
@@ -0,0 +1,68 @@
+import { useState } from "react";
+import { invoke } from "@tauri-apps/api/tauri";
+import { Clock } from "lucide-react";
+
+// Used for tasks without a time estimate
+const DEFAULT_DURATION_MINUTES = 30;
+const SHOWN_SLOTS = 5;
+
+const formatSlot = (slot) => {
+  const start = new Date(slot.start);
+  return `${start.toLocaleDateString("en-US", {
+    weekday: "short",
+    day: "2-digit",
+    month: "short",
+  })} ${start.toLocaleTimeString("en-US", {
+    hour: "2-digit",
+    minute: "2-digit",
+  })}`;
+};
+
+// Free slots in the calendar long enough for the task; picking one moves the
+// task to that day.
+const SuggestedSlots = ({ task, onPick }) => {
+  const [slots, setSlots] = useState(null);
+  const [error, setError] = useState("");
+
+  const findSlots = async () => {
+    setError("");
+    try {
+      const options = task.estimated_minutes
+        ? { task_id: task.id, max_slots: SHOWN_SLOTS }
+        : { duration_minutes: DEFAULT_DURATION_MINUTES, max_slots: SHOWN_SLOTS };
+      setSlots(await invoke("find_free_slots", { options }));
+    } catch (error) {
+      console.error("Error finding free slots:", error);
+      setError(String(error));
+    }
+  };
+
+  return (
+    <div className="flex flex-wrap items-center gap-2 text-sm mb-4">
+      <button
+        onClick={findSlots}
+        className="flex items-center gap-2 py-2 px-4 rounded-xl bg-white/5 border border-white/20 text-white hover:border-cyan-500/30 transition-all duration-300"
+      >
+        <Clock size={15} className="text-cyan-400" />
+        <span className="font-light tracking-wide">Find time</span>
+      </button>
+
+      {error && <span className="text-red-500">{error}</span>}
+      {slots && slots.length === 0 && (
+        <span className="text-gray-400 italic">No free slot this week</span>
+      )}
+      {slots?.map((slot) => (
+        <button
+          key={slot.start}
+          onClick={() => onPick(slot)}
+          title={`${slot.minutes} min free`}
+          className="py-1.5 px-3 rounded-lg bg-cyan-500/10 border border-cyan-500/20 text-cyan-100 hover:bg-cyan-500/20 transition-all duration-300"
+        >
+          {formatSlot(slot)}
+        </button>
+      ))}
+    </div>
+  );
+};
+
+export default SuggestedSlots;
@@ -1,7 +1,127 @@
-import React from "react";
+import React, { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/tauri";
+import { ScaleLoader } from "react-spinners";
+
+const cardClass = `w-full bg-gradient-to-br from-white/5 to-black/10
+                   backdrop-blur-md border border-white/10 rounded-xl
+                   shadow-lg p-4`;
+
+const StatCard = ({ label, value }) => (
+  <div className={cardClass}>
+    <p className="text-sm text-gray-400">{label}</p>
+    <p className="text-3xl font-semibold mt-1">{value}</p>
+  </div>
+);
+
+const BarSeries = ({ title, entries, labelKey }) => {
+  const max = Math.max(1, ...entries.map((entry) => entry.completed));
+
+  return (
+    <div className={cardClass}>
+      <h2 className="text-xl font-semibold mb-3">{title}</h2>
+      <div className="flex items-end gap-1 h-32">
+        {entries.map((entry) => (
+          <div
+            key={entry[labelKey]}
+            className="flex-1 bg-cyan-400/70 rounded-t"
+            style={{ height: `${(entry.completed / max) * 100}%` }}
+            title={`${entry[labelKey]}: ${entry.completed}`}
+          />
+        ))}
+      </div>
+    </div>
+  );
+};
+
+const formatHours = (hours) => {
+  if (hours === null || hours === undefined) return "-";
+  if (hours < 24) return `${hours.toFixed(1)}h`;
+  return `${(hours / 24).toFixed(1)}d`;
+};
 
 const GameProgress = () => {
-  return <div>GameProgress</div>;
+  const [stats, setStats] = useState(null);
+  const [error, setError] = useState("");
+
+  useEffect(() => {
+    const fetchStats = async () => {
+      try {
+        setStats(await invoke("get_task_stats"));
+      } catch (error) {
+        console.error("Error loading task stats:", error);
+        setError(String(error));
+      }
+    };
+
+    fetchStats();
+  }, []);
+
+  if (error) {
+    return <p className="text-red-400">{error}</p>;
+  }
+
+  if (!stats) {
+    return (
+      <div className="flex items-center justify-center mt-20">
+        <ScaleLoader color="#8dccff" />
+      </div>
+    );
+  }
+
+  return (
+    <div className="flex flex-col gap-5">
+      <div className="grid grid-cols-2 md:grid-cols-4 gap-5">
+        <StatCard label="Completed today" value={stats.completed_today} />
+        <StatCard label="This week" value={stats.completed_this_week} />
+        <StatCard
+          label="Streak"
+          value={`${stats.current_streak} / ${stats.longest_streak}`}
+        />
+        <StatCard label="Overdue" value={stats.overdue} />
+        <StatCard label="Open" value={stats.open} />
+        <StatCard label="Completed" value={stats.completed} />
+        <StatCard
+          label="Avg. time to complete"
+          value={formatHours(stats.average_completion_hours)}
+        />
+        <StatCard label="Total tasks" value={stats.total} />
+      </div>
+
+      <BarSeries
+        title="Completed per day"
+        entries={stats.per_day}
+        labelKey="date"
+      />
+      <BarSeries
+        title="Completed per week"
+        entries={stats.per_week}
+        labelKey="week_start"
+      />
+
+      <div className={cardClass}>
+        <h2 className="text-xl font-semibold mb-3">Projects</h2>
+        <ul className="space-y-2">
+          {stats.per_project.map((project) => (
+            <li
+              key={project.project_id || project.project}
+              className="flex justify-between"
+            >
+              <span>{project.project}</span>
+              <span className="text-gray-400">
+                {project.completed} done · {project.open} open
+                {project.overdue > 0 && (
+                  <span className="text-red-400">
+                    {" "}
+                    · {project.overdue} overdue
+                  </span>
+                )}
+              </span>
+            </li>
+          ))}
+        </ul>
+      </div>
+    </div>
+  );
 };
 
 export default GameProgress;
@@ -2,6 +2,8 @@ import React, { useEffect, useState, useRef, lazy, Suspense } from "react";
 import { Link } from "react-router-dom";
 import { invoke } from "@tauri-apps/api/tauri";
 import { syncLocalTasksWithFirestore } from "../utils/syncLocalTasks";
+import { syncTimeEntriesWithFirestore } from "../utils/syncTimeEntries";
+import { loadProjectNames } from "../utils/buildProjectOptions";
 
 const SelectedLocalTaskModal = lazy(() =>
   import("../components/local-tasks/SelectedLocalTaskModal")
@@ -78,6 +80,7 @@ function ProjectCard({
 const Projects = () => {
   const [tasks, setTasks] = useState([]);
   const [groupedTasks, setGroupedTasks] = useState({});
+  const [projectNames, setProjectNames] = useState([]);
   const [selectedTask, setSelectedTask] = useState(null);
   const [editMode, setEditMode] = useState(false);
   const [errorMessage, setErrorMessage] = useState("");
@@ -87,12 +90,31 @@ const Projects = () => {
 
   const saveTasks = async (updatedTasks) => {
     try {
-      await invoke("save_local_tasks", { tasks: updatedTasks });
+      // The backend rolls subtask completion up to parents and archives old tasks
+      const savedTasks = await invoke("save_local_tasks", {
+        tasks: updatedTasks,
+      });
+      setTasks(savedTasks);
+      return savedTasks;
     } catch (error) {
       console.error("Error saving tasks:", error);
     }
   };
 
+  const syncTimeEntries = async () => {
+    try {
+      const entries = await invoke("list_time_entries");
+      await syncTimeEntriesWithFirestore(
+        entries,
+        () => {},
+        (updatedEntries) =>
+          invoke("save_time_entries", { entries: updatedEntries })
+      );
+    } catch (error) {
+      console.error("Error syncing time entries:", error);
+    }
+  };
+
   const checkOnlineStatus = async () => {
     try {
       await fetch("https://firebase.google.com", { mode: "no-cors" });
@@ -113,6 +135,7 @@ const Projects = () => {
           await checkOnlineStatus();
           if (isOnline) {
             await syncLocalTasksWithFirestore(loadedTasks, setTasks, saveTasks);
+            await syncTimeEntries();
           }
         }
       } catch (error) {
@@ -123,15 +146,42 @@ const Projects = () => {
     fetchTasks();
   }, []);
 
+  // Open tasks with a project, filtered and sorted by the backend
   useEffect(() => {
-    const groups = tasks.reduce((acc, task) => {
-      if (task.completed) return acc;
-      if (!task.project) return acc;
-      if (!acc[task.project]) acc[task.project] = [];
-      acc[task.project].push(task);
-      return acc;
-    }, {});
-    setGroupedTasks(groups);
+    let cancelled = false;
+
+    const loadGroups = async () => {
+      try {
+        const groups = {};
+        let cursor = null;
+        do {
+          const page = await invoke("query_tasks", {
+            query: {
+              filter: { completed: false, has_project: true },
+              sort: [{ key: "project" }, { key: "date" }, { key: "title" }],
+              cursor,
+              limit: 500,
+            },
+          });
+          for (const task of page.tasks) {
+            (groups[task.project] ??= []).push(task);
+          }
+          cursor = page.next_cursor;
+        } while (cursor);
+
+        if (!cancelled) setGroupedTasks(groups);
+      } catch (error) {
+        console.error("Error querying project tasks:", error);
+      }
+    };
+
+    loadGroups();
+    loadProjectNames()
+      .then((names) => !cancelled && setProjectNames(names))
+      .catch((error) => console.error("Error loading projects:", error));
+    return () => {
+      cancelled = true;
+    };
   }, [tasks]);
 
   useEffect(() => {
@@ -146,6 +196,7 @@ const Projects = () => {
           console.error("Error syncing tasks:", error);
         }
       }
+      await syncTimeEntries();
     };
 
     syncPendingTasks();
@@ -156,17 +207,39 @@ const Projects = () => {
 
     await checkOnlineStatus();
 
-    const updatedTasks = tasks.map((task) => {
-      if (task.project === oldProjectName) {
-        return {
-          ...task,
-          project: newProjectName.trim(),
-          updated_at: new Date().toISOString(),
-          pending_sync: !isOnline,
-        };
+    const projectId = tasks.find(
+      (task) => task.project === oldProjectName && task.project_id
+    )?.project_id;
+
+    let updatedTasks;
+    if (projectId) {
+      try {
+        const renamedTasks = await invoke("rename_project", {
+          id: projectId,
+          name: newProjectName.trim(),
+        });
+        updatedTasks = renamedTasks.map((task) =>
+          task.project_id === projectId
+            ? { ...task, pending_sync: !isOnline }
+            : task
+        );
+      } catch (error) {
+        console.error("Error renaming project:", error);
+        return;
       }
-      return task;
-    });
+    } else {
+      updatedTasks = tasks.map((task) => {
+        if (task.project === oldProjectName) {
+          return {
+            ...task,
+            project: newProjectName.trim(),
+            updated_at: new Date().toISOString(),
+            pending_sync: !isOnline,
+          };
+        }
+        return task;
+      });
+    }
 
     setTasks(updatedTasks);
     await saveTasks(updatedTasks);
@@ -359,7 +432,7 @@ const Projects = () => {
             taskIsComplete={selectedTask.completed}
             handleTaskComplete={handleTaskComplete}
             processTaskDescription={(text) => text}
-            projects={[...new Set(tasks.map((t) => t.project).filter(Boolean))]}
+            projects={projectNames}
             isOnline={isOnline}
           />
         )}
@@ -1,3 +1,11 @@
+import { invoke } from "@tauri-apps/api/tauri";
+
+// Names of the active projects, from the backend's project list
+export const loadProjectNames = async () => {
+  const projects = await invoke("list_projects");
+  return projects.map((project) => project.name);
+};
+
 // Convert your projects array to react-select friendly options:
 export const buildProjectOptions = (projects) =>
   projects.map((proj) => ({
@@ -0,0 +1,141 @@
+import { collection, getDocs, doc, writeBatch } from "firebase/firestore";
+import { db } from "../config/firebase";
+
+// Two-way sync of a local store with a Firestore collection. The newer
+// updated_at wins, and deletions are tracked with timestamps in a separate
+// collection so they reach other devices.
+//
+// Ids in `skipIds` stay in Firestore but are neither pulled nor treated as
+// deleted locally; ids in `purgeIds` are deleted everywhere. `onCommitted`
+// runs once the Firestore batch has been written.
+export async function syncCollectionWithFirestore(
+  { collectionName, deletedCollectionName, skipIds, purgeIds, onCommitted },
+  localItems,
+  setLocalItems,
+  saveLocalItems
+) {
+  const skipped = skipIds || new Set();
+  const purged = purgeIds || new Set();
+
+  // Get all current items from Firestore
+  const snapshot = await getDocs(collection(db, collectionName));
+  const firestoreItems = [];
+  snapshot.forEach((docSnap) => {
+    firestoreItems.push({ id: docSnap.id, ...docSnap.data() });
+  });
+  const firestoreById = new Map(firestoreItems.map((item) => [item.id, item]));
+
+  // Get deleted items tracking information
+  const deletedSnapshot = await getDocs(
+    collection(db, deletedCollectionName)
+  );
+  const deletedItems = new Map();
+  deletedSnapshot.forEach((docSnap) => {
+    deletedItems.set(docSnap.id, docSnap.data().deleted_at);
+  });
+
+  const localMap = new Map();
+  localItems.forEach((item) => {
+    localMap.set(item.id, item);
+  });
+
+  const batch = writeBatch(db);
+
+  // Check for items that were deleted on other devices
+  for (const [itemId, deletedAt] of deletedItems) {
+    const localItem = localMap.get(itemId);
+    if (localItem) {
+      const localUpdateTime = new Date(localItem.updated_at || 0);
+      const deleteTime = new Date(deletedAt);
+
+      if (deleteTime > localUpdateTime) {
+        // Delete was more recent than local update
+        localMap.delete(itemId);
+      } else {
+        // Local update was more recent, remove from deleted tracking
+        batch.delete(doc(db, deletedCollectionName, itemId));
+      }
+    }
+  }
+
+  for (const itemId of purged) {
+    localMap.delete(itemId);
+    batch.delete(doc(db, collectionName, itemId));
+    batch.set(doc(db, deletedCollectionName, itemId), {
+      deleted_at: new Date().toISOString(),
+    });
+  }
+
+  // Sync from Firestore to local
+  for (const remoteItem of firestoreItems) {
+    if (skipped.has(remoteItem.id) || purged.has(remoteItem.id)) {
+      continue;
+    }
+    const localItem = localMap.get(remoteItem.id);
+    if (!localItem) {
+      // New item from Firestore
+      if (!deletedItems.has(remoteItem.id)) {
+        localMap.set(remoteItem.id, remoteItem);
+      }
+    } else {
+      const remoteTime = new Date(remoteItem.updated_at || 0);
+      const localTime = new Date(localItem.updated_at || 0);
+
+      if (remoteTime > localTime) {
+        localMap.set(remoteItem.id, remoteItem);
+      }
+    }
+  }
+
+  // Sync from local to Firestore
+  const itemsToSync = Array.from(localMap.values());
+  for (const item of itemsToSync) {
+    const matchingFirestoreItem = firestoreById.get(item.id);
+
+    if (!matchingFirestoreItem) {
+      // New local item
+      if (!deletedItems.has(item.id)) {
+        batch.set(doc(db, collectionName, item.id), item);
+      }
+    } else {
+      const localTime = new Date(item.updated_at || 0);
+      const remoteTime = new Date(matchingFirestoreItem.updated_at || 0);
+
+      if (localTime > remoteTime) {
+        batch.set(doc(db, collectionName, item.id), item);
+      }
+    }
+  }
+
+  // Handle local deletions
+  const localIds = new Set(itemsToSync.map((item) => item.id));
+  for (const remoteItem of firestoreItems) {
+    if (
+      !localIds.has(remoteItem.id) &&
+      !deletedItems.has(remoteItem.id) &&
+      !skipped.has(remoteItem.id) &&
+      !purged.has(remoteItem.id)
+    ) {
+      // Item was deleted locally, track it
+      batch.delete(doc(db, collectionName, remoteItem.id));
+      batch.set(doc(db, deletedCollectionName, remoteItem.id), {
+        deleted_at: new Date().toISOString(),
+      });
+    }
+  }
+
+  // Commit all changes
+  await batch.commit();
+  if (onCommitted) {
+    await onCommitted();
+  }
+
+  // Update local state
+  const updatedItems = itemsToSync.map((item) => ({
+    ...item,
+    pending_sync: false,
+  }));
+
+  setLocalItems(updatedItems);
+  await saveLocalItems(updatedItems);
+}
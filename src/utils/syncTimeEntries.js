@@ -0,0 +1,22 @@
+import { syncCollectionWithFirestore } from "./syncCollection";
+
+export async function syncTimeEntriesWithFirestore(
+  localEntries,
+  setLocalEntries,
+  saveLocalEntries
+) {
+  try {
+    await syncCollectionWithFirestore(
+      {
+        collectionName: "Time Entries",
+        deletedCollectionName: "Deleted Time Entries",
+      },
+      localEntries,
+      setLocalEntries,
+      saveLocalEntries
+    );
+  } catch (error) {
+    console.error("Error syncing time entries with Firestore:", error);
+    throw error;
+  }
+}